@@ -1,7 +1,7 @@
 extern crate str_sim;
 
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use itertools::Itertools;
 use str_sim::{levenshtein_distance, sim_jaro_winkler};
 
@@ -20,6 +20,35 @@ pub fn calc_jaro_winkler_distance(s1: &str, s2: &str) -> f64 {
 }
 
 
+// 计算两个字符串的共有字符数(按字符计数的多重集交集大小)，编辑距离不小于 max(len1, len2) - 共有字符数
+pub fn calc_common_char_count(s1: &str, s2: &str) -> usize{
+    let mut ascii_count = [0i64; 128];
+    let mut other_count: HashMap<char, i64> = HashMap::new();
+    for c in s1.chars(){
+        if c.is_ascii(){
+            ascii_count[c as usize] += 1;
+        }else {
+            *(other_count.entry(c).or_insert(0)) += 1;
+        }
+    }
+    let mut result = 0;
+    for c in s2.chars(){
+        let count = if c.is_ascii() { &mut ascii_count[c as usize] } else { other_count.entry(c).or_insert(0) };
+        if *count > 0{
+            *count -= 1;
+            result += 1;
+        }
+    }
+    result
+}
+
+
+// 计算两个字符串的最长公共前缀长度(按字符计数)
+pub fn calc_common_prefix_length(s1: &str, s2: &str) -> usize{
+    s1.chars().zip(s2.chars()).take_while(|(a, b)| a == b).count()
+}
+
+
 // 计算两个数字之间的相似度，即对数字之间相差的大小进行量化运算
 pub fn calc_similarity_between_digits(dig_a: i64, dig_b: i64) -> f64{
     let min = (cmp::min(dig_a, dig_b) + 1) as f64;
@@ -29,9 +58,10 @@ pub fn calc_similarity_between_digits(dig_a: i64, dig_b: i64) -> f64{
 
 
 // 分析dif_list, 对其量化打分，各项值越小代表两字符串越相似，计算出的分数越高   例如[0,7,9]的分数高于[5,7,9]
-fn calc_score_by_analyze_dif_list(dif_list: &Vec<i64>) -> f64{
+fn calc_score_by_analyze_dif_list(dif_list: &[i64]) -> f64{
     let mut result = 0.0;
-    let mut statistics_table: HashMap<i64, i64> = HashMap::new();
+    // 使用有序映射，保证浮点累加顺序固定，计算结果可复现
+    let mut statistics_table: BTreeMap<i64, i64> = BTreeMap::new();
 
     // 生成统计数据映射表
     for i in dif_list{
@@ -49,18 +79,19 @@ fn calc_score_by_analyze_dif_list(dif_list: &Vec<i64>) -> f64{
 }
 
 
-// 计算两个i64 vec之间的相似度分数     (连续性相似度分析 + 内容相似度分析)
-fn calc_similarity_score_between_i64vecs(vec_a: &Vec<i64>, vec_b: &Vec<i64>) -> f64{
+// 计算两个编码序列之间的相似度分数     (连续性相似度分析 + 内容相似度分析)
+// 编码可以是i64，也可以是更紧凑的u8/u32，计算时统一转换为i64
+fn calc_similarity_score_between_code_slices<A: Copy + Into<i64>, B: Copy + Into<i64>>(vec_a: &[A], vec_b: &[B]) -> f64{
     let mut dif_list = Vec::new(); // 存储差值的集合
     let mut count_continuous_same: i64 = 0;  // 连续相同的字符数
     let mut continuity_analysis_result = 0.0000000000001;  //连续性相似度分析
     let min_vec_length = cmp::min(vec_a.len(), vec_b.len());
 
     for index in 0..min_vec_length{
-        let differ = (vec_a[index] - vec_b[index]).abs();
+        let differ = (vec_a[index].into() - vec_b[index].into()).abs();
         dif_list.push(differ);
 
-        continuity_analysis_result += (2.0 / (differ + 1) as f64) as f64;
+        continuity_analysis_result += 2.0 / (differ + 1) as f64;
         if differ == 0{
             count_continuous_same += 1;
         }else{
@@ -74,16 +105,22 @@ fn calc_similarity_score_between_i64vecs(vec_a: &Vec<i64>, vec_b: &Vec<i64>) ->
 }
 
 
+// 计算两个编码序列之间的相似度
+pub fn calc_similarity_between_code_slices<A: Copy + Into<i64>, B: Copy + Into<i64>>(vec_a: &[A], vec_b: &[B]) -> f64 {
+    calc_similarity_score_between_code_slices(vec_a, vec_b) * 2.0 /
+        (calc_similarity_score_between_code_slices(vec_a, vec_a) + calc_similarity_score_between_code_slices(vec_b, vec_b))
+}
+
+
 // 计算两个i64 vec之间的相似度
 // 传入&Vec<i64>，若需要计算字符串之间的相似度，则需要逐字符转换成ASCII码，并生成Vec<i64>数据列作为参数传入
-pub fn calc_similarity_between_i64vecs(vec_a: &Vec<i64>, vec_b: &Vec<i64>) -> f64 {
-    calc_similarity_score_between_i64vecs(vec_a, vec_b) * 2.0 /
-        (calc_similarity_score_between_i64vecs(vec_a, vec_a) + calc_similarity_score_between_i64vecs(vec_b, vec_b))
+pub fn calc_similarity_between_i64vecs(vec_a: &[i64], vec_b: &[i64]) -> f64 {
+    calc_similarity_between_code_slices(vec_a, vec_b)
 }
 
 
 // 计算两个Vec<i64> vec之间的相似度分数
-fn calc_similarity_score_between_vvecs(vec_a: &Vec<Vec<i64>>, vec_b: &Vec<Vec<i64>>) -> f64 {
+fn calc_similarity_score_between_vvecs(vec_a: &[Vec<i64>], vec_b: &[Vec<i64>]) -> f64 {
     let mut score = 0.0000000000001;
     let iter_times = cmp::min(vec_a.len(), vec_b.len());
     for index in 0..iter_times{
@@ -95,12 +132,59 @@ fn calc_similarity_score_between_vvecs(vec_a: &Vec<Vec<i64>>, vec_b: &Vec<Vec<i6
 
 // 计算两个Vec<i64> vec之间的相似度分数
 // 传入&Vec<&Vec<i64>>，若需要计算字符串之间的相似度，则需要逐字符转换成ASCII码，并生成Vec<i64>数据列作为参数传入
-pub fn calc_similarity_between_vvecs(vec_a: &Vec<Vec<i64>>, vec_b: &Vec<Vec<i64>>) -> f64 {
+pub fn calc_similarity_between_vvecs(vec_a: &[Vec<i64>], vec_b: &[Vec<i64>]) -> f64 {
     calc_similarity_score_between_vvecs(vec_a, vec_b) * 2.0 /
         (calc_similarity_score_between_vvecs(vec_a, vec_a) + calc_similarity_score_between_vvecs(vec_b, vec_b))
 }
 
 
+// 计算两个紧凑零件集合之间的相似度分数
+// 零件集合由连续存储的字符编码codes及各零件的长度part_sizes表示，与calc_similarity_score_between_vvecs的结果一致
+fn calc_similarity_score_between_part_lists<A: Copy + Into<i64>, B: Copy + Into<i64>>(codes_a: &[A], part_sizes_a: &[u32], codes_b: &[B], part_sizes_b: &[u32]) -> f64 {
+    let mut score = 0.0000000000001;
+    let (mut offset_a, mut offset_b) = (0, 0);
+    for (size_a, size_b) in part_sizes_a.iter().zip(part_sizes_b.iter()){
+        let (size_a, size_b) = (*size_a as usize, *size_b as usize);
+        score += calc_similarity_between_code_slices(&codes_a[offset_a..offset_a + size_a], &codes_b[offset_b..offset_b + size_b]) * ((size_a + size_b) / 2).pow(2) as f64;
+        offset_a += size_a;
+        offset_b += size_b;
+    }
+    score
+}
+
+
+// 计算两个紧凑零件集合之间的相似度
+// 传入连续存储的字符编码以及各零件的长度，避免为每个零件单独分配内存
+pub fn calc_similarity_between_part_lists<A: Copy + Into<i64>, B: Copy + Into<i64>>(codes_a: &[A], part_sizes_a: &[u32], codes_b: &[B], part_sizes_b: &[u32]) -> f64 {
+    calc_similarity_score_between_part_lists(codes_a, part_sizes_a, codes_b, part_sizes_b) * 2.0 /
+        (calc_similarity_score_between_part_lists(codes_a, part_sizes_a, codes_a, part_sizes_a) +
+            calc_similarity_score_between_part_lists(codes_b, part_sizes_b, codes_b, part_sizes_b))
+}
+
+
+// 提取邮箱地址的本地部分(最后一个'@'之前的内容)，不含'@'时原样返回
+// 例如："john.smith88@gmail.com" -> "john.smith88"
+pub fn extract_email_local_part(account_name: &str) -> &str{
+    match account_name.rfind('@') {
+        Some(index) => &account_name[..index],
+        None => account_name
+    }
+}
+
+
+// 将账号名按数据类型分割为紧凑的骨架信息: 骨架样式(b's'为字母等非数字字符, b'i'为数字)及各零件长度(按字符计数)
+// 例如："lalala1234lala4t" -> ([s, i, s, i, s], [6, 4, 4, 1, 1])
+pub fn split_account_name_compactly(account_name: &str) -> (Vec<u8>, Vec<u32>){
+    let mut res_skeleton_style: Vec<u8> = Vec::new();
+    let mut res_skeleton_part_size_list: Vec<u32> = Vec::new();
+    for (b_digit, group) in &account_name.chars().group_by(|elt| elt.is_ascii_digit()) {
+        res_skeleton_style.push(if b_digit { b'i' } else { b's' });
+        res_skeleton_part_size_list.push(group.count() as u32);
+    }
+    (res_skeleton_style, res_skeleton_part_size_list)
+}
+
+
 // 将用户名根据数据类型进行分割，如果遇到英文字符则转换成ASCII码，若遇到阿拉伯数字则直接拼接成最大数字
 // 例如："lalala1234lala4t" -> [[108, 97, 108, 97, 108, 97], [1234], [108, 97, 108, 97], [4], [116]]
 pub fn split_account_name_by_data_type(account_name: &str) -> (Vec<Vec<i64>>, Vec<i64>, Vec<i64>){
@@ -108,7 +192,7 @@ pub fn split_account_name_by_data_type(account_name: &str) -> (Vec<Vec<i64>>, Ve
     let mut res_skeleton_style:Vec<i64> = Vec::new();
     let mut res_skeleton_part_size_list:Vec<i64> = Vec::new();
 
-    for (_key, group) in &account_name.chars().into_iter().group_by(|elt| '0' <= *elt && *elt <= '9') {
+    for (_key, group) in &account_name.chars().group_by(|elt| '0' <= *elt && *elt <= '9') {
         let tmp_vec = group.collect::<Vec<char>>();
 
         res_item_list.push(Vec::new());
@@ -139,14 +223,22 @@ mod tests {
         assert_eq!(calc_edit_distance("asdsf", "asdsq"), 1);
         assert_eq!(calc_jaro_winkler_distance("asdsf", "asdsq"), 0.9555555555555556);
         assert_eq!(calc_similarity_between_digits(124, 127), 0.9762813290793214);
-        assert_eq!(calc_score_by_analyze_dif_list(&vec![5, 7, 9]), 4.0227272727272725);
-        assert_eq!(calc_similarity_score_between_i64vecs(&vec![5, 7, 9], &vec![6, 7, 8]), 44.5000000000001);
-        assert_eq!(calc_similarity_between_i64vecs(&vec![5, 7, 9], &vec![6, 7, 8]), 0.5855263157894742);
-        assert_eq!(calc_similarity_score_between_vvecs(&vec![vec![5, 7, 9], vec![97, 99]], &vec![vec![6, 7, 8], vec![98, 100]]), 6.436403508772047);
-        assert_eq!(calc_similarity_between_vvecs(&vec![vec![5, 7, 9], vec![97, 99]], &vec![vec![6, 7, 8], vec![98, 100]]), 0.4951079622132306);
+        assert_eq!(calc_common_char_count("ubut2739", "tubu9273a"), 8);
+        assert_eq!(calc_common_char_count("aab", "abb"), 2);
+        assert_eq!(calc_common_prefix_length("ubut2739", "ubut2839"), 5);
+        assert_eq!(calc_score_by_analyze_dif_list(&[5, 7, 9]), 4.0227272727272725);
+        assert_eq!(calc_similarity_score_between_code_slices(&[5i64, 7, 9], &[6i64, 7, 8]), 44.5000000000001);
+        assert_eq!(calc_similarity_between_code_slices(&[5u8, 7, 9], &[6u32, 7, 8]), 0.5855263157894742);
+        assert_eq!(calc_similarity_between_i64vecs(&[5, 7, 9], &[6, 7, 8]), 0.5855263157894742);
+        assert_eq!(calc_similarity_score_between_vvecs(&[vec![5, 7, 9], vec![97, 99]], &[vec![6, 7, 8], vec![98, 100]]), 6.436403508772047);
+        assert_eq!(calc_similarity_between_vvecs(&[vec![5, 7, 9], vec![97, 99]], &[vec![6, 7, 8], vec![98, 100]]), 0.4951079622132306);
+        assert_eq!(calc_similarity_between_part_lists(&[5u8, 7, 9, 97, 99], &[3, 2], &[6u8, 7, 8, 98, 100], &[3, 2]), 0.4951079622132306);
+        assert_eq!(extract_email_local_part("john.smith88@gmail.com"), "john.smith88");
+        assert_eq!(extract_email_local_part("john.smith88"), "john.smith88");
+        assert_eq!(split_account_name_compactly("lalala1234lala4t"), (b"sisis".to_vec(), vec![6, 4, 4, 1, 1]));
         println!("{:?}", split_account_name_by_data_type("lalala1234lala4t"));
-        println!("{:?}", calc_similarity_between_i64vecs(&vec![117], &vec![116]));
-        println!("{}", calc_similarity_between_vvecs(&vec![vec![5, 7, 9], vec![97, 99]], &vec![vec![6, 7, 8], vec![98, 100]]));
-        println!("{}", calc_similarity_between_vvecs(&vec![vec![117]], &vec![vec![116]]));
+        println!("{:?}", calc_similarity_between_i64vecs(&[117], &[116]));
+        println!("{}", calc_similarity_between_vvecs(&[vec![5, 7, 9], vec![97, 99]], &[vec![6, 7, 8], vec![98, 100]]));
+        println!("{}", calc_similarity_between_vvecs(&[vec![117]], &[vec![116]]));
     }
 }
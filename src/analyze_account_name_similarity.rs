@@ -1,9 +1,16 @@
-use lazy_static::lazy_static;
+use std::borrow::Cow;
+use crate::calibration::CSimilarityCalibrator;
+use crate::score_combiner::{ScoreCombiner, CScoreCombineContext, CEditDistanceJaroCombiner};
 use crate::algorithm::{calc_edit_distance, calc_jaro_winkler_distance, calc_similarity_between_digits,
-                       calc_similarity_between_i64vecs, calc_similarity_between_vvecs, split_account_name_by_data_type};
+                       calc_similarity_between_code_slices, calc_similarity_between_part_lists, split_account_name_compactly,
+                       calc_common_char_count, calc_common_prefix_length, extract_email_local_part};
+
+// 上界计算的浮点误差容限，保证上界不会因舍入误差而低于真实值
+const UPPER_BOUND_TOLERANCE: f64 = 1e-9;
 
 // 相似度细节
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CAccountNameSimResultDetail{
     pub sim_total_score: f64,
     pub sim_score: f64,
@@ -17,47 +24,299 @@ pub struct CAccountNameSimResultDetail{
 }
 
 // 量化相似度过程中各参数的权重表
+// 各项权重必须为非负有限数且总和大于0，可通过new进行校验
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CAccountNameSimAnalyseParamsWeightTable{
-    pub skeleton_skeleton_style: i64,
-    pub skeleton_part_size_list: i64,
-    pub length:  i64,
-    pub item_list: i64,
-    pub item_amount: i64,
+    pub skeleton_skeleton_style: f64,
+    pub skeleton_part_size_list: f64,
+    pub length:  f64,
+    pub item_list: f64,
+    pub item_amount: f64,
 }
 
-// 对相似度量化计算的封装
+// 账号名的比较方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NameComparison {
+    #[default]
+    WholeName,          // 比较完整的账号名
+    EmailLocalPart      // 含'@'的账号名只比较extract_email_local_part提取的本地部分，域名不参与计算
+}
+impl NameComparison {
+    /// 账号名中实际参与比较的部分
+    ///
+    /// The part of the account name that is actually compared
+    pub fn compared_part<'n>(&self, account_name: &'n str) -> &'n str{
+        match self {
+            NameComparison::WholeName => account_name,
+            NameComparison::EmailLocalPart => extract_email_local_part(account_name)
+        }
+    }
+}
+
+/// 预设: 名称、权重表以及账号名的比较方式，可通过名称从配置中选取
+///
+/// A preset: the name, the weight table and how account names are compared. It can be picked by name from config
+#[derive(Debug, Clone, PartialEq)]
+pub struct CWeightTablePreset{
+    pub name: &'static str,
+    pub weight_table: CAccountNameSimAnalyseParamsWeightTable,
+    pub name_comparison: NameComparison
+}
+
+// 权重表校验/加载过程中的错误
 #[derive(Debug)]
-pub struct CAccountNameSimAnalyse<'a>{
-    pub account_name: &'a str,
-    pub length: i64,
-    pub item_list:  Vec<Vec<i64>>,
-    pub item_amount: i64,
-    pub skeleton_style: Vec<i64>,
-    pub skeleton_part_size_list: Vec<i64>
+pub enum WeightTableError {
+    InvalidWeight(&'static str, f64),   // 某项权重为负数或非有限数
+    ZeroWeightSum,                      // 权重总和为0
+    UnknownPreset(String),              // 不存在该名称的预设权重表
+    UnsupportedFormat(String),          // 无法根据文件扩展名判断格式
+    Parse(String),                      // JSON/TOML解析失败
+    Io(std::io::Error)
 }
-impl Clone for CAccountNameSimAnalyse<'_> {
-    fn clone(&self) -> Self {
-        CAccountNameSimAnalyse {
-            account_name: self.account_name,
-            length: self.length,
-            item_list: self.item_list.clone(),
-            item_amount: self.item_amount,
-            skeleton_style: self.skeleton_style.clone(),
-            skeleton_part_size_list: self.skeleton_part_size_list.clone()
+impl std::fmt::Display for WeightTableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeightTableError::InvalidWeight(name, weight) => write!(f, "weight `{}` must be a non-negative finite number, got {}", name, weight),
+            WeightTableError::ZeroWeightSum => write!(f, "the sum of all weights must be greater than 0"),
+            WeightTableError::UnknownPreset(name) => write!(f, "unknown weight table preset `{}`", name),
+            WeightTableError::UnsupportedFormat(path) => write!(f, "unsupported weight table file format: {}", path),
+            WeightTableError::Parse(msg) => write!(f, "failed to parse weight table: {}", msg),
+            WeightTableError::Io(err) => write!(f, "{}", err)
         }
     }
 }
+impl std::error::Error for WeightTableError {}
+
+// 对相似度量化计算的封装
+// 零件集合以紧凑形式保存: 全部字符编码连续存储，skeleton_part_size_list同时作为各零件的偏移表使用
+// 纯ASCII账号名直接复用account_name的字节作为编码，不额外分配内存；含非ASCII字符时才保存u32编码
+// 通过new创建的分析对象独占各项数据；CAccountNameAnaVec中的分析对象借用其共享存储中的数据，不单独分配内存
+// 不兼容变更(见CHANGELOG.md): length/item_amount由i64改为u32, skeleton_style由Vec<i64>改为Cow<[u8]>,
+// skeleton_part_size_list由Vec<i64>改为Cow<[u32]>, 字段item_list改为方法item_list()
+#[derive(Debug, Clone)]
+pub struct CAccountNameSimAnalyse<'a>{
+    pub account_name: &'a str,
+    pub length: u32,
+    pub item_amount: u32,
+    pub skeleton_style: Cow<'a, [u8]>,              // 各零件的类型: b'i'为数字, b's'为其他字符
+    pub skeleton_part_size_list: Cow<'a, [u32]>,    // 各零件的字符数量
+    wide_char_codes: Option<Cow<'a, [u32]>>         // 仅非ASCII账号名存在，保存全部字符的编码
+}
+
+// 字符编码的两种存储形式
+enum CharCodes<'b>{
+    Ascii(&'b [u8]),
+    Wide(&'b [u32])
+}
 
-// 默认参数权重表
-lazy_static! {
-    static ref DEFAULT_PARAMETER_WEIGHT_TABLE: CAccountNameSimAnalyseParamsWeightTable =
+/// 默认参数权重表
+///
+/// The default parameter weight table
+pub const DEFAULT_PARAMETER_WEIGHT_TABLE: CAccountNameSimAnalyseParamsWeightTable =
     CAccountNameSimAnalyseParamsWeightTable{
-        skeleton_skeleton_style: 7,
-        skeleton_part_size_list: 9,
-        length:  1,
-        item_list: 8,
-        item_amount: 3,
+        skeleton_skeleton_style: 7.0,
+        skeleton_part_size_list: 9.0,
+        length:  1.0,
+        item_list: 8.0,
+        item_amount: 3.0,
     };
+
+/// 均匀参数权重表，各项权重相同，可作为对比基线
+///
+/// The uniform parameter weight table, all weights are equal. Useful as a baseline for comparison
+pub const UNIFORM_PARAMETER_WEIGHT_TABLE: CAccountNameSimAnalyseParamsWeightTable =
+    CAccountNameSimAnalyseParamsWeightTable{
+        skeleton_skeleton_style: 1.0,
+        skeleton_part_size_list: 1.0,
+        length:  1.0,
+        item_list: 1.0,
+        item_amount: 1.0,
+    };
+
+/// 顺序计数器型账号(例如ubut1001、ubut1002)的权重表: 同一批账号的骨架与各零件长度完全一致，仅数字部分不同，
+/// 因此提高骨架与零件长度的权重，降低零件内容的权重
+///
+/// The weight table for sequential-counter farms (e.g. ubut1001, ubut1002): accounts of one batch share the skeleton and the part sizes and only differ in the digits,
+/// so the skeleton and part-size weights are raised and the part content weight is lowered
+pub const SEQUENTIAL_COUNTER_PARAMETER_WEIGHT_TABLE: CAccountNameSimAnalyseParamsWeightTable =
+    CAccountNameSimAnalyseParamsWeightTable{
+        skeleton_skeleton_style: 8.0,
+        skeleton_part_size_list: 12.0,
+        length:  2.0,
+        item_list: 2.0,
+        item_amount: 4.0,
+    };
+
+/// 随机字符串型账号(例如x7kq9m2p、b3nf8z1w)的权重表: 同一生成器产生的账号长度与字母/数字的排列方式一致，但字符内容随机，
+/// 因此提高长度、骨架与零件长度的权重，几乎不考虑零件内容
+///
+/// The weight table for random-string farms (e.g. x7kq9m2p, b3nf8z1w): accounts from one generator share the length and the letter/digit pattern but the characters are random,
+/// so the length, skeleton and part-size weights are raised and the part content is almost ignored
+pub const RANDOM_STRING_PARAMETER_WEIGHT_TABLE: CAccountNameSimAnalyseParamsWeightTable =
+    CAccountNameSimAnalyseParamsWeightTable{
+        skeleton_skeleton_style: 8.0,
+        skeleton_part_size_list: 10.0,
+        length:  6.0,
+        item_list: 1.0,
+        item_amount: 4.0,
+    };
+
+/// 邮箱型账号的权重表: 同一批账号通常复用相同的名字片段，因此以零件内容为主要依据。
+/// 应当只比较邮箱的本地部分，名为email的预设以NameComparison::EmailLocalPart使用该权重表
+///
+/// The weight table for email farms: accounts of one batch usually reuse the same name fragments, so the part content is the main signal.
+/// It is meant for the local parts of email addresses, and the preset named email uses it with NameComparison::EmailLocalPart
+pub const EMAIL_PARAMETER_WEIGHT_TABLE: CAccountNameSimAnalyseParamsWeightTable =
+    CAccountNameSimAnalyseParamsWeightTable{
+        skeleton_skeleton_style: 3.0,
+        skeleton_part_size_list: 3.0,
+        length:  1.0,
+        item_list: 12.0,
+        item_amount: 2.0,
+    };
+
+/// 全部预设，第一个为默认权重表
+///
+/// All presets, the first one is the default weight table
+pub const WEIGHT_TABLE_PRESETS: [CWeightTablePreset; 5] = [
+    CWeightTablePreset{ name: "default", weight_table: DEFAULT_PARAMETER_WEIGHT_TABLE, name_comparison: NameComparison::WholeName },
+    CWeightTablePreset{ name: "uniform", weight_table: UNIFORM_PARAMETER_WEIGHT_TABLE, name_comparison: NameComparison::WholeName },
+    CWeightTablePreset{ name: "sequential_counter", weight_table: SEQUENTIAL_COUNTER_PARAMETER_WEIGHT_TABLE, name_comparison: NameComparison::WholeName },
+    CWeightTablePreset{ name: "random_string", weight_table: RANDOM_STRING_PARAMETER_WEIGHT_TABLE, name_comparison: NameComparison::WholeName },
+    CWeightTablePreset{ name: "email", weight_table: EMAIL_PARAMETER_WEIGHT_TABLE, name_comparison: NameComparison::EmailLocalPart },
+];
+
+impl CWeightTablePreset {
+
+    /// 返回全部预设的名称
+    ///
+    /// Return the names of all presets
+    pub fn names() -> Vec<&'static str>{
+        WEIGHT_TABLE_PRESETS.iter().map(|preset| preset.name).collect()
+    }
+
+    /// 根据名称获取预设
+    ///
+    /// Get a preset by name
+    pub fn from_name(name: &str) -> Result<Self, WeightTableError>{
+        WEIGHT_TABLE_PRESETS.iter()
+            .find(|preset| preset.name == name)
+            .cloned()
+            .ok_or_else(|| WeightTableError::UnknownPreset(name.to_string()))
+    }
+}
+
+impl Default for CAccountNameSimAnalyseParamsWeightTable {
+    fn default() -> Self {
+        DEFAULT_PARAMETER_WEIGHT_TABLE
+    }
+}
+
+impl CAccountNameSimAnalyseParamsWeightTable {
+
+    /// 创建权重表并校验: 各项权重必须为非负有限数且总和大于0
+    ///
+    /// Create a weight table and validate it: every weight must be a non-negative finite number and the sum must be greater than 0
+    pub fn new(skeleton_skeleton_style: f64, skeleton_part_size_list: f64, length: f64, item_list: f64, item_amount: f64) -> Result<Self, WeightTableError>{
+        let table = CAccountNameSimAnalyseParamsWeightTable{ skeleton_skeleton_style, skeleton_part_size_list, length, item_list, item_amount };
+        table.validate()?;
+        Ok(table)
+    }
+
+    /// 校验权重表
+    ///
+    /// Validate the weight table
+    pub fn validate(&self) -> Result<(), WeightTableError>{
+        for (name, weight) in self.named_weights().iter(){
+            if !weight.is_finite() || *weight < 0.0{
+                return Err(WeightTableError::InvalidWeight(name, *weight));
+            }
+        }
+        if self.weight_sum() <= 0.0{
+            return Err(WeightTableError::ZeroWeightSum);
+        }
+        Ok(())
+    }
+
+    /// 各项权重之和
+    ///
+    /// The sum of all weights
+    pub fn weight_sum(&self) -> f64{
+        self.length + self.item_list + self.item_amount + self.skeleton_skeleton_style + self.skeleton_part_size_list
+    }
+
+    /// 返回(字段名, 权重)列表
+    ///
+    /// Return the list of (field name, weight)
+    pub fn named_weights(&self) -> [(&'static str, f64); 5]{
+        [("skeleton_skeleton_style", self.skeleton_skeleton_style),
+         ("skeleton_part_size_list", self.skeleton_part_size_list),
+         ("length", self.length),
+         ("item_list", self.item_list),
+         ("item_amount", self.item_amount)]
+    }
+}
+
+#[cfg(feature = "serde")]
+impl CAccountNameSimAnalyseParamsWeightTable {
+
+    /// 从JSON字符串中加载权重表并校验
+    ///
+    /// Load a weight table from a JSON string and validate it
+    pub fn from_json_str(json_str: &str) -> Result<Self, WeightTableError>{
+        let table: Self = serde_json::from_str(json_str).map_err(|e| WeightTableError::Parse(e.to_string()))?;
+        table.validate()?;
+        Ok(table)
+    }
+
+    /// 序列化为JSON字符串
+    ///
+    /// Serialize to a JSON string
+    pub fn to_json_string(&self) -> String{
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// 从TOML字符串中加载权重表并校验
+    ///
+    /// Load a weight table from a TOML string and validate it
+    pub fn from_toml_str(toml_str: &str) -> Result<Self, WeightTableError>{
+        let table: Self = toml::from_str(toml_str).map_err(|e| WeightTableError::Parse(e.to_string()))?;
+        table.validate()?;
+        Ok(table)
+    }
+
+    /// 序列化为TOML字符串
+    ///
+    /// Serialize to a TOML string
+    pub fn to_toml_string(&self) -> String{
+        toml::to_string(self).unwrap()
+    }
+
+    /// 从文件中加载权重表，根据扩展名(.json/.toml)判断格式
+    ///
+    /// Load a weight table from a file, the format is determined by the extension (.json/.toml)
+    pub fn load_from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, WeightTableError>{
+        let content = std::fs::read_to_string(path.as_ref()).map_err(WeightTableError::Io)?;
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json_str(&content),
+            Some("toml") => Self::from_toml_str(&content),
+            _ => Err(WeightTableError::UnsupportedFormat(path.as_ref().display().to_string()))
+        }
+    }
+
+    /// 将权重表保存到文件，根据扩展名(.json/.toml)判断格式
+    ///
+    /// Save the weight table to a file, the format is determined by the extension (.json/.toml)
+    pub fn save_to_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), WeightTableError>{
+        let content = match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("json") => self.to_json_string(),
+            Some("toml") => self.to_toml_string(),
+            _ => return Err(WeightTableError::UnsupportedFormat(path.as_ref().display().to_string()))
+        };
+        std::fs::write(path.as_ref(), content).map_err(WeightTableError::Io)
+    }
 }
 
 impl Default for CAccountNameSimResultDetail {
@@ -76,18 +335,106 @@ impl Default for CAccountNameSimResultDetail {
     }
 }
 
+impl CAccountNameSimResultDetail {
+    // 按字段声明顺序将各项细节转换为特征向量(编辑距离转换为f64)
+    pub fn to_feature_vec(&self) -> Vec<f64>{
+        vec![self.sim_total_score,
+             self.sim_score,
+             self.sim_jaro_distance,
+             self.sim_edit_distance as f64,
+             self.sim_length,
+             self.sim_item_list,
+             self.sim_item_amount,
+             self.sim_skeleton_style,
+             self.sim_skeleton_part_size_list]
+    }
+}
+
+// 根据权重表及已计算好的各分项相似度计算sim_score，再通过组合器计算sim_total_score
+// 权重总和不大于0的权重表无法加权，此时sim_score记为0，避免产生NaN
+pub(crate) fn combine_result_detail(detail: &mut CAccountNameSimResultDetail,
+                                    weight_table: &CAccountNameSimAnalyseParamsWeightTable,
+                                    context: &CScoreCombineContext,
+                                    combiner: &dyn ScoreCombiner){
+    let weight_sum = weight_table.weight_sum();
+    detail.sim_score = if weight_sum > 0.0 {
+        (detail.sim_length * weight_table.length +
+            detail.sim_item_list * weight_table.item_list +
+            detail.sim_item_amount * weight_table.item_amount +
+            detail.sim_skeleton_style * weight_table.skeleton_skeleton_style +
+            detail.sim_skeleton_part_size_list * weight_table.skeleton_part_size_list) / weight_sum
+    } else {
+        0.0
+    };
+
+    detail.sim_total_score = combiner.combine(detail, context);
+}
+
 impl<'a> CAccountNameSimAnalyse<'a>{
 
     // init
-    pub fn new(_account_name: &'a str) -> CAccountNameSimAnalyse{
-        let split_res = split_account_name_by_data_type(_account_name);
+    pub fn new(_account_name: &'a str) -> CAccountNameSimAnalyse<'a>{
+        let (skeleton_style, skeleton_part_size_list) = split_account_name_compactly(_account_name);
         CAccountNameSimAnalyse{
             account_name: _account_name,
-            length: _account_name.len() as i64,
-            item_amount: split_res.0.len() as i64,
-            item_list: split_res.0,
-            skeleton_style: split_res.1,
-            skeleton_part_size_list: split_res.2,
+            length: _account_name.len() as u32,
+            item_amount: skeleton_style.len() as u32,
+            skeleton_style: Cow::Owned(skeleton_style),
+            skeleton_part_size_list: Cow::Owned(skeleton_part_size_list),
+            wide_char_codes: if _account_name.is_ascii() { None } else { Some(Cow::Owned(_account_name.chars().map(|c| c as u32).collect())) }
+        }
+    }
+
+    // 借用已解析好的各项数据创建分析对象，不分配内存，用于CAccountNameAnaVec的共享存储
+    pub(crate) fn from_borrowed_parts(account_name: &'a str, skeleton_style: &'a [u8], skeleton_part_size_list: &'a [u32], wide_char_codes: Option<&'a [u32]>) -> CAccountNameSimAnalyse<'a>{
+        CAccountNameSimAnalyse{
+            account_name,
+            length: account_name.len() as u32,
+            item_amount: skeleton_style.len() as u32,
+            skeleton_style: Cow::Borrowed(skeleton_style),
+            skeleton_part_size_list: Cow::Borrowed(skeleton_part_size_list),
+            wide_char_codes: wide_char_codes.map(Cow::Borrowed)
+        }
+    }
+
+    // 非ASCII账号名的字符编码，纯ASCII账号名为None
+    pub(crate) fn wide_char_codes(&self) -> Option<&[u32]>{
+        self.wide_char_codes.as_deref()
+    }
+
+    /// 零件集合，每个零件为其字符编码列表。分析对象内部以紧凑形式保存，此方法会重新分配内存
+    ///
+    /// The item list where each item is the list of its char codes. It is stored compactly inside the analysis object, so this allocates a new list
+    pub fn item_list(&self) -> Vec<Vec<i64>>{
+        let codes: Vec<i64> = match self.char_codes() {
+            CharCodes::Ascii(codes) => codes.iter().map(|c| *c as i64).collect(),
+            CharCodes::Wide(codes) => codes.iter().map(|c| *c as i64).collect()
+        };
+        let mut offset = 0;
+        self.skeleton_part_size_list.iter().map(|size| {
+            offset += *size as usize;
+            codes[offset - *size as usize..offset].to_vec()
+        }).collect()
+    }
+
+    /// 分析对象占用的内存大小(字节)，包括结构体本身及其独占的堆内存，不包括借用的账号名及借用的共享存储
+    ///
+    /// The memory used by the analysis object in bytes, including the struct itself and the heap memory it owns, but neither the borrowed account name nor borrowed shared storage
+    pub fn memory_footprint(&self) -> usize{
+        let owned_size = |b_owned: bool, size: usize| if b_owned { size } else { 0 };
+        std::mem::size_of::<Self>() +
+            owned_size(matches!(self.skeleton_style, Cow::Owned(_)), self.skeleton_style.len() * std::mem::size_of::<u8>()) +
+            owned_size(matches!(self.skeleton_part_size_list, Cow::Owned(_)), self.skeleton_part_size_list.len() * std::mem::size_of::<u32>()) +
+            match &self.wide_char_codes {
+                Some(Cow::Owned(codes)) => codes.len() * std::mem::size_of::<u32>(),
+                _ => 0
+            }
+    }
+
+    fn char_codes(&self) -> CharCodes<'_>{
+        match self.wide_char_codes() {
+            Some(codes) => CharCodes::Wide(codes),
+            None => CharCodes::Ascii(self.account_name.as_bytes())
         }
     }
 
@@ -102,63 +449,151 @@ impl<'a> CAccountNameSimAnalyse<'a>{
     }
 
     // 计算零件集合相似度
-    fn analyze_similarity_item_list(&self, item_list: &Vec<Vec<i64>>) -> f64{
-        calc_similarity_between_vvecs(&self.item_list, item_list)
+    fn analyze_similarity_item_list(&self, obj_to_cmp: &CAccountNameSimAnalyse) -> f64{
+        let (sizes_a, sizes_b) = (&self.skeleton_part_size_list, &obj_to_cmp.skeleton_part_size_list);
+        match (self.char_codes(), obj_to_cmp.char_codes()) {
+            (CharCodes::Ascii(a), CharCodes::Ascii(b)) => calc_similarity_between_part_lists(a, sizes_a, b, sizes_b),
+            (CharCodes::Ascii(a), CharCodes::Wide(b)) => calc_similarity_between_part_lists(a, sizes_a, b, sizes_b),
+            (CharCodes::Wide(a), CharCodes::Ascii(b)) => calc_similarity_between_part_lists(a, sizes_a, b, sizes_b),
+            (CharCodes::Wide(a), CharCodes::Wide(b)) => calc_similarity_between_part_lists(a, sizes_a, b, sizes_b)
+        }
     }
 
     // 计算骨架零件相似度
-    fn analyze_similarity_skeleton_part_size_list(&self, item_list: &Vec<i64>) -> f64 {
-        calc_similarity_between_i64vecs(&self.skeleton_part_size_list, item_list)
+    fn analyze_similarity_skeleton_part_size_list(&self, part_size_list: &[u32]) -> f64 {
+        calc_similarity_between_code_slices(&self.skeleton_part_size_list, part_size_list)
     }
 
     // 计算骨架相似度
-    fn analyze_similarity_skeleton_style(&self, skeleton: &Vec<i64>) -> f64{
-        calc_similarity_between_i64vecs(&self.skeleton_style, skeleton)
+    fn analyze_similarity_skeleton_style(&self, skeleton: &[u8]) -> f64{
+        calc_similarity_between_code_slices(&self.skeleton_style, skeleton)
     }
 
     // 计算零件数量相似度
-    fn analyze_similarity_item_amount(&self, item_amount: i64) -> f64{
-        calc_similarity_between_digits(self.item_amount, item_amount)
+    fn analyze_similarity_item_amount(&self, item_amount: u32) -> f64{
+        calc_similarity_between_digits(self.item_amount as i64, item_amount as i64)
     }
 
     // 计算字符串长度相似度
-    fn analyze_similarity_length(&self, length: i64) -> f64{
-        calc_similarity_between_digits(self.length, length)
+    fn analyze_similarity_length(&self, length: u32) -> f64{
+        calc_similarity_between_digits(self.length as i64, length as i64)
     }
 
     // 计算两个账号名称的相似度（需要传入参数权重表）
     pub fn calc_similarity_by_specify_param_weights(&self,
                                                 obj_to_cmp: &CAccountNameSimAnalyse,
                                                 weight_table: &CAccountNameSimAnalyseParamsWeightTable) -> (f64, CAccountNameSimResultDetail){
+        self.calc_similarity_by_specify_combiner(obj_to_cmp, weight_table, &CEditDistanceJaroCombiner)
+    }
+
+    // 计算两个账号名称的相似度（需要传入参数权重表及分数组合器）
+    pub fn calc_similarity_by_specify_combiner(&self,
+                                               obj_to_cmp: &CAccountNameSimAnalyse,
+                                               weight_table: &CAccountNameSimAnalyseParamsWeightTable,
+                                               combiner: &dyn ScoreCombiner) -> (f64, CAccountNameSimResultDetail){
 
-        let mut ret_detail: CAccountNameSimResultDetail = Default::default();
-
-        ret_detail.sim_length = self.analyze_similarity_length(obj_to_cmp.length);
-        ret_detail.sim_item_amount = self.analyze_similarity_item_amount(obj_to_cmp.item_amount);
-        ret_detail.sim_skeleton_style = self.analyze_similarity_skeleton_style(&obj_to_cmp.skeleton_style);
-        ret_detail.sim_skeleton_part_size_list = self.analyze_similarity_skeleton_part_size_list(&obj_to_cmp.skeleton_part_size_list);
-        ret_detail.sim_item_list = self.analyze_similarity_item_list(&obj_to_cmp.item_list);
-        ret_detail.sim_edit_distance = self.analyze_edit_distance(obj_to_cmp.account_name);
-        ret_detail.sim_jaro_distance = self.analyze_jaro_distance(obj_to_cmp.account_name);
-        ret_detail.sim_score = (ret_detail.sim_length * weight_table.length  as f64 +
-            ret_detail.sim_item_list * weight_table.item_list  as f64 +
-            ret_detail.sim_item_amount * weight_table.item_amount  as f64 +
-            ret_detail.sim_skeleton_style * weight_table.skeleton_skeleton_style  as f64 +
-            ret_detail.sim_skeleton_part_size_list * weight_table.skeleton_part_size_list  as f64) /
-            (weight_table.length + weight_table.item_list + weight_table.item_amount +
-                weight_table.skeleton_skeleton_style + weight_table.skeleton_part_size_list)  as f64;
-        let average_len: f64 = ((self.account_name.len() + obj_to_cmp.account_name.len()) / 2) as f64;
-
-        ret_detail.sim_total_score = (ret_detail.sim_score * ret_detail.sim_edit_distance as f64 +
-            average_len * ret_detail.sim_jaro_distance) /
-            (average_len + ret_detail.sim_edit_distance as f64);
+        let mut ret_detail = CAccountNameSimResultDetail {
+            sim_length: self.analyze_similarity_length(obj_to_cmp.length),
+            sim_item_amount: self.analyze_similarity_item_amount(obj_to_cmp.item_amount),
+            sim_skeleton_style: self.analyze_similarity_skeleton_style(&obj_to_cmp.skeleton_style),
+            sim_skeleton_part_size_list: self.analyze_similarity_skeleton_part_size_list(&obj_to_cmp.skeleton_part_size_list),
+            sim_item_list: self.analyze_similarity_item_list(obj_to_cmp),
+            sim_edit_distance: self.analyze_edit_distance(obj_to_cmp.account_name),
+            sim_jaro_distance: self.analyze_jaro_distance(obj_to_cmp.account_name),
+            ..Default::default()
+        };
+        combine_result_detail(&mut ret_detail, weight_table, &CScoreCombineContext::new(self, obj_to_cmp), combiner);
 
         (ret_detail.sim_total_score,  ret_detail)
     }
 
+    // 按预设计算两个账号名称的相似度: 按预设的比较方式取出实际参与比较的部分，再使用预设的权重表计算
+    pub fn calc_similarity_by_preset(&self, obj_to_cmp: &CAccountNameSimAnalyse, preset: &CWeightTablePreset) -> (f64, CAccountNameSimResultDetail){
+        match preset.name_comparison {
+            NameComparison::WholeName => self.calc_similarity_by_specify_param_weights(obj_to_cmp, &preset.weight_table),
+            NameComparison::EmailLocalPart => CAccountNameSimAnalyse::new(preset.name_comparison.compared_part(self.account_name))
+                .calc_similarity_by_specify_param_weights(&CAccountNameSimAnalyse::new(preset.name_comparison.compared_part(obj_to_cmp.account_name)), &preset.weight_table)
+        }
+    }
+
     // 计算两个账号名称的相似度
     pub fn calc_similarity(&self, obj_to_cmp: &CAccountNameSimAnalyse) -> (f64, CAccountNameSimResultDetail){
-        self.calc_similarity_by_specify_param_weights(obj_to_cmp, &*DEFAULT_PARAMETER_WEIGHT_TABLE)
+        self.calc_similarity_by_specify_param_weights(obj_to_cmp, &DEFAULT_PARAMETER_WEIGHT_TABLE)
+    }
+
+    // 计算sim_total_score的上界（需要传入参数权重表，仅适用于默认的分数组合器CEditDistanceJaroCombiner）
+    // 只使用长度、零件数量、骨架等低成本分项，以及由共有字符数推导出的编辑距离下界、jaro-winkler相似度上界，
+    // 零件集合相似度按其最大值1计算，不会进行编辑距离、jaro-winkler及零件集合相似度的完整计算
+    pub fn calc_similarity_upper_bound_by_specify_param_weights(&self,
+                                                                obj_to_cmp: &CAccountNameSimAnalyse,
+                                                                weight_table: &CAccountNameSimAnalyseParamsWeightTable) -> f64{
+        let mut bound_detail = CAccountNameSimResultDetail {
+            sim_length: self.analyze_similarity_length(obj_to_cmp.length),
+            sim_item_amount: self.analyze_similarity_item_amount(obj_to_cmp.item_amount),
+            sim_skeleton_style: self.analyze_similarity_skeleton_style(&obj_to_cmp.skeleton_style),
+            sim_skeleton_part_size_list: self.analyze_similarity_skeleton_part_size_list(&obj_to_cmp.skeleton_part_size_list),
+            sim_item_list: 1.0,
+            ..Default::default()
+        };
+        let context = CScoreCombineContext::new(self, obj_to_cmp);
+        combine_result_detail(&mut bound_detail, weight_table, &context, &CEditDistanceJaroCombiner);
+
+        // jaro-winkler相似度上界: 匹配字符数不超过共有字符数，且换位数取0；公共前缀长度可精确计算
+        let char_len_a = self.account_name.chars().count();
+        let char_len_b = obj_to_cmp.account_name.chars().count();
+        let common_char_count = calc_common_char_count(self.account_name, obj_to_cmp.account_name);
+        bound_detail.sim_jaro_distance = if char_len_a == 0 && char_len_b == 0 {
+            1.0
+        } else if common_char_count == 0 {
+            0.0
+        } else {
+            let jaro = (common_char_count as f64 / char_len_a as f64 + common_char_count as f64 / char_len_b as f64 + 1.0) / 3.0;
+            let prefix_weight = 2.0 / (char_len_a as f64 + char_len_b as f64 + 2.0);
+            let prefix_factor = (prefix_weight * calc_common_prefix_length(self.account_name, obj_to_cmp.account_name) as f64).clamp(0.0, 1.0);
+            jaro + prefix_factor * (1.0 - jaro)
+        };
+
+        // 组合结果关于编辑距离单调，因此在编辑距离的上下界处取最大值即可
+        let max_char_len = char_len_a.max(char_len_b);
+        let mut result: f64 = 0.0;
+        for edit_distance in [max_char_len - common_char_count, max_char_len].iter(){
+            bound_detail.sim_edit_distance = *edit_distance as i64;
+            result = result.max(CEditDistanceJaroCombiner.combine(&bound_detail, &context));
+        }
+        result + UPPER_BOUND_TOLERANCE
+    }
+
+    // 计算sim_total_score的上界
+    pub fn calc_similarity_upper_bound(&self, obj_to_cmp: &CAccountNameSimAnalyse) -> f64{
+        self.calc_similarity_upper_bound_by_specify_param_weights(obj_to_cmp, &DEFAULT_PARAMETER_WEIGHT_TABLE)
+    }
+
+    // 判断两个账号名称的相似度是否不低于阈值，是则返回相似度及细节，否则返回None
+    // 先计算低成本的上界，上界已低于阈值时直接返回None，跳过编辑距离、jaro-winkler及零件集合相似度的计算
+    pub fn calc_similarity_at_least(&self, obj_to_cmp: &CAccountNameSimAnalyse, threshold: f64) -> Option<(f64, CAccountNameSimResultDetail)>{
+        if self.calc_similarity_upper_bound(obj_to_cmp) < threshold{
+            return None;
+        }
+        let result = self.calc_similarity(obj_to_cmp);
+        if result.0 >= threshold { Some(result) } else { None }
+    }
+
+    // 计算两个账号名称的相似度，并通过校准模型将其转换为两者属于同一组的概率
+    // 返回 (原始相似度, 校准后的概率, 相似度细节)
+    // 使用默认的权重表及分数组合器，校准模型也应在其上拟合(例如通过CSimilarityCalibrator::fit)
+    pub fn calc_calibrated_similarity(&self, obj_to_cmp: &CAccountNameSimAnalyse, calibrator: &CSimilarityCalibrator) -> (f64, f64, CAccountNameSimResultDetail){
+        self.calc_calibrated_similarity_by_specify_combiner(obj_to_cmp, &DEFAULT_PARAMETER_WEIGHT_TABLE, &CEditDistanceJaroCombiner, calibrator)
+    }
+
+    // 计算两个账号名称的相似度（需要传入参数权重表及分数组合器），并通过校准模型将其转换为两者属于同一组的概率
+    // 校准模型应在同一权重表及分数组合器的计算结果上拟合(例如通过CSimilarityCalibrator::fit_by_specify_combiner)
+    pub fn calc_calibrated_similarity_by_specify_combiner(&self,
+                                                          obj_to_cmp: &CAccountNameSimAnalyse,
+                                                          weight_table: &CAccountNameSimAnalyseParamsWeightTable,
+                                                          combiner: &dyn ScoreCombiner,
+                                                          calibrator: &CSimilarityCalibrator) -> (f64, f64, CAccountNameSimResultDetail){
+        let (sim, detail) = self.calc_similarity_by_specify_combiner(obj_to_cmp, weight_table, combiner);
+        (sim, calibrator.calibrate(&detail), detail)
     }
 }
 
@@ -168,12 +603,103 @@ mod tests {
     use super::*;
     #[test]
     fn it_works() {
-        assert_eq!(CAccountNameSimAnalyse::new("u0j2e9u1s2h8l91").analyze_similarity_item_list(&CAccountNameSimAnalyse::new("t9x1h8y0b7g6f42").item_list), 0.23419743655039468);
+        assert_eq!(CAccountNameSimAnalyse::new("u0j2e9u1s2h8l91").analyze_similarity_item_list(&CAccountNameSimAnalyse::new("t9x1h8y0b7g6f42")), 0.23419743655039468);
         assert_eq!(CAccountNameSimAnalyse::new("u0j2e9u1s2h8l91").calc_similarity(&CAccountNameSimAnalyse::new("t9x1h8y0b7g6f42")).0, 0.6072663004595803);
         println!("{:?}", CAccountNameSimAnalyse::new("u0j2e9u1s2h8l91").calc_similarity(&CAccountNameSimAnalyse::new("t9x1h8y0b7g6f42")));
         println!("{:?}", CAccountNameSimAnalyse::new("33xwb656").calc_similarity(&CAccountNameSimAnalyse::new("33xwb778")));
         println!("{:?}", CAccountNameSimAnalyse::new("u0j2e9u1s2h8l91"));
         println!("{:?}", CAccountNameSimAnalyse::new("ubut2222").calc_similarity(&CAccountNameSimAnalyse::new("ubut1057")));
 
+        // 相似度上界不低于真实相似度
+        let names = ["ubut2739", "ubut1179", "ubut2739a", "zz2871369", "33xwb656", "u0j2e9u1s2h8l91", "htgt4303", "", "用户2739"];
+        for name_a in names.iter(){
+            for name_b in names.iter(){
+                let (obj_a, obj_b) = (CAccountNameSimAnalyse::new(name_a), CAccountNameSimAnalyse::new(name_b));
+                assert!(obj_a.calc_similarity_upper_bound(&obj_b) >= obj_a.calc_similarity(&obj_b).0);
+            }
+        }
+        assert!(CAccountNameSimAnalyse::new("ubut2739").calc_similarity_upper_bound(&CAccountNameSimAnalyse::new("zz2871369")) < 0.856);
+        assert!(CAccountNameSimAnalyse::new("ubut2739").calc_similarity_at_least(&CAccountNameSimAnalyse::new("zz2871369"), 0.856).is_none());
+        assert_eq!(CAccountNameSimAnalyse::new("ubut2222").calc_similarity_at_least(&CAccountNameSimAnalyse::new("ubut1057"), 0.5).unwrap().0,
+                   CAccountNameSimAnalyse::new("ubut2222").calc_similarity(&CAccountNameSimAnalyse::new("ubut1057")).0);
+
+        // 紧凑存储与原有的Vec<Vec<i64>>存储计算结果一致，并统计内存占用
+        let account_names = std::fs::read_to_string("test_data/test_account_list.txt").unwrap();
+        let account_names: Vec<&str> = account_names.lines().take(2000).chain(names.iter().copied()).collect();
+        let mut legacy_size = 0;
+        for (index, name) in account_names.iter().enumerate(){
+            let obj = CAccountNameSimAnalyse::new(name);
+            let (item_list, skeleton_style, skeleton_part_size_list) = crate::algorithm::split_account_name_by_data_type(name);
+            assert_eq!(obj.item_list(), item_list);
+            assert_eq!(obj.skeleton_style.iter().map(|c| *c as i64).collect::<Vec<i64>>(), skeleton_style);
+            assert_eq!(obj.skeleton_part_size_list.iter().map(|c| *c as i64).collect::<Vec<i64>>(), skeleton_part_size_list);
+            let obj_to_cmp = CAccountNameSimAnalyse::new(account_names[(index * 7 + 1) % account_names.len()]);
+            // calc_score_by_analyze_dif_list按BTreeMap的键序累加，两种存储的计算结果逐位一致
+            assert_eq!(obj.analyze_similarity_item_list(&obj_to_cmp), crate::algorithm::calc_similarity_between_vvecs(&item_list, &obj_to_cmp.item_list()));
+
+            // 原有布局: &str + 2个i64 + 3个Vec, 每个零件一个Vec<i64>
+            legacy_size += std::mem::size_of::<&str>() + 2 * 8 + 3 * std::mem::size_of::<Vec<i64>>() +
+                item_list.len() * (std::mem::size_of::<Vec<i64>>() + 2 * 8) + item_list.iter().map(|item| item.len() * 8).sum::<usize>();
+        }
+        // CAccountNameAnaVec中的分析对象借用共享存储，各账号名只占用位置信息及其零件数据
+        let ana = crate::group_account_name_by_similarity::CAccountNameAnaVec::new(&account_names);
+        assert!(ana.memory_footprint() * 4 < legacy_size);
+        assert!(ana.analyses().iter().all(|obj| obj.memory_footprint() == std::mem::size_of::<CAccountNameSimAnalyse>()));
+        assert!(ana.analyses().iter().zip(ana.to_vec()).all(|(obj, name)| obj.item_list() == CAccountNameSimAnalyse::new(&name).item_list()));
+        // 纯ASCII账号名不保存字符编码，非ASCII账号名每个字符4字节
+        let base_size = std::mem::size_of::<CAccountNameSimAnalyse>();
+        assert_eq!(CAccountNameSimAnalyse::new("ubut2739").memory_footprint(), base_size + 2 + 2 * 4);
+        assert_eq!(CAccountNameSimAnalyse::new("用户2739").memory_footprint(), base_size + 2 + 2 * 4 + 6 * 4);
+
+        // 权重表校验
+        assert_eq!(CAccountNameSimAnalyseParamsWeightTable::new(7.0, 9.0, 1.0, 8.0, 3.0).unwrap(), CAccountNameSimAnalyseParamsWeightTable::default());
+        assert!(matches!(CAccountNameSimAnalyseParamsWeightTable::new(0.0, 0.0, 0.0, 0.0, 0.0), Err(WeightTableError::ZeroWeightSum)));
+        assert!(matches!(CAccountNameSimAnalyseParamsWeightTable::new(1.0, -1.0, 0.0, 0.0, 0.0), Err(WeightTableError::InvalidWeight("skeleton_part_size_list", _))));
+        assert!(matches!(CAccountNameSimAnalyseParamsWeightTable::new(1.0, 1.0, f64::NAN, 0.0, 0.0), Err(WeightTableError::InvalidWeight("length", _))));
+        assert_eq!(CWeightTablePreset::from_name("uniform").unwrap().weight_table, UNIFORM_PARAMETER_WEIGHT_TABLE);
+        assert!(CWeightTablePreset::from_name("unknown").is_err());
+        assert_eq!(CWeightTablePreset::names(), vec!["default", "uniform", "sequential_counter", "random_string", "email"]);
+        for name in CWeightTablePreset::names(){
+            assert!(CWeightTablePreset::from_name(name).unwrap().weight_table.validate().is_ok());
+        }
+
+        // 各预设权重表在代表性账号上的表现
+        let sim_by_table = |name_a: &str, name_b: &str, table: &CAccountNameSimAnalyseParamsWeightTable|
+            CAccountNameSimAnalyse::new(name_a).calc_similarity_by_specify_param_weights(&CAccountNameSimAnalyse::new(name_b), table).0;
+        assert!(sim_by_table("ubut1001", "ubut2750", &SEQUENTIAL_COUNTER_PARAMETER_WEIGHT_TABLE) > sim_by_table("ubut1001", "ubut2750", &DEFAULT_PARAMETER_WEIGHT_TABLE));
+        assert!(sim_by_table("x7kq9m2p", "b3nf8z1w", &RANDOM_STRING_PARAMETER_WEIGHT_TABLE) > sim_by_table("x7kq9m2p", "b3nf8z1w", &DEFAULT_PARAMETER_WEIGHT_TABLE));
+        assert!(sim_by_table("x7kq9m2p", "b3nf8z1w", &RANDOM_STRING_PARAMETER_WEIGHT_TABLE) > sim_by_table("x7kq9m2p", "b3nf8z1w", &SEQUENTIAL_COUNTER_PARAMETER_WEIGHT_TABLE));
+        assert!(sim_by_table("john.smith88", "john.smith1990", &EMAIL_PARAMETER_WEIGHT_TABLE) > sim_by_table("john.smith88", "john.smith1990", &DEFAULT_PARAMETER_WEIGHT_TABLE));
+        assert!(sim_by_table("john.smith88", "mary_lee2020", &EMAIL_PARAMETER_WEIGHT_TABLE) < sim_by_table("john.smith88", "mary_lee2020", &DEFAULT_PARAMETER_WEIGHT_TABLE));
+        // 邮箱型预设只比较本地部分: 完整地址与本地部分的结果一致，不同域名不影响相似度
+        let email_preset = CWeightTablePreset::from_name("email").unwrap();
+        assert_eq!(email_preset.name_comparison, NameComparison::EmailLocalPart);
+        assert!(WEIGHT_TABLE_PRESETS.iter().filter(|preset| preset.name != "email").all(|preset| preset.name_comparison == NameComparison::WholeName));
+        let sim_by_preset = |name_a: &str, name_b: &str, preset: &CWeightTablePreset|
+            CAccountNameSimAnalyse::new(name_a).calc_similarity_by_preset(&CAccountNameSimAnalyse::new(name_b), preset).0;
+        assert_eq!(sim_by_preset("john.smith88@gmail.com", "john.smith1990@outlook.com", &email_preset),
+                   sim_by_table("john.smith88", "john.smith1990", &EMAIL_PARAMETER_WEIGHT_TABLE));
+        assert_eq!(sim_by_preset("john.smith88@gmail.com", "mary_lee2020@gmail.com", &email_preset),
+                   sim_by_table("john.smith88", "mary_lee2020", &EMAIL_PARAMETER_WEIGHT_TABLE));
+        assert_eq!(sim_by_preset("john.smith88@gmail.com", "john.smith88", &email_preset), 1.0);
+        // 权重表本身总是比较完整的账号名
+        assert!(sim_by_table("john.smith88@gmail.com", "john.smith88", &EMAIL_PARAMETER_WEIGHT_TABLE) < 1.0);
+        assert_eq!(sim_by_preset("ubut1001", "ubut2750", &WEIGHT_TABLE_PRESETS[0]), sim_by_table("ubut1001", "ubut2750", &DEFAULT_PARAMETER_WEIGHT_TABLE));
+        let zero_table = CAccountNameSimAnalyseParamsWeightTable{ skeleton_skeleton_style: 0.0, skeleton_part_size_list: 0.0, length: 0.0, item_list: 0.0, item_amount: 0.0 };
+        assert!(!CAccountNameSimAnalyse::new("ubut2222").calc_similarity_by_specify_param_weights(&CAccountNameSimAnalyse::new("ubut1057"), &zero_table).0.is_nan());
+
+        #[cfg(feature = "serde")]
+        {
+            let table = CAccountNameSimAnalyseParamsWeightTable::new(1.5, 2.0, 0.0, 4.25, 1.0).unwrap();
+            assert_eq!(CAccountNameSimAnalyseParamsWeightTable::from_json_str(&table.to_json_string()).unwrap(), table);
+            assert_eq!(CAccountNameSimAnalyseParamsWeightTable::from_toml_str(&table.to_toml_string()).unwrap(), table);
+            assert!(CAccountNameSimAnalyseParamsWeightTable::from_toml_str(&zero_table.to_toml_string()).is_err());
+            assert_eq!(CAccountNameSimAnalyseParamsWeightTable::from_toml_str(&EMAIL_PARAMETER_WEIGHT_TABLE.to_toml_string()).unwrap(), EMAIL_PARAMETER_WEIGHT_TABLE);
+            let path = std::env::temp_dir().join("account_name_similarity_weight_table.toml");
+            table.save_to_file(&path).unwrap();
+            assert_eq!(CAccountNameSimAnalyseParamsWeightTable::load_from_file(&path).unwrap(), table);
+            std::fs::remove_file(&path).unwrap();
+        }
+
     }
 }
\ No newline at end of file
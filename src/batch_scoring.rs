@@ -0,0 +1,139 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use rayon::prelude::*;
+use crate::analyze_account_name_similarity::{CAccountNameSimAnalyse, CAccountNameSimResultDetail};
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+
+
+// 一对多相似度查询的参数
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CBatchScoringOptions{
+    pub top_k: Option<usize>,           // 仅返回相似度最高的k个结果, None表示不限制数量
+    pub threshold_sim: Option<f64>,     // 仅返回相似度高于等于该阈值的结果, None表示不限制
+    pub b_with_detail: bool             // 是否在结果中附带相似度细节
+}
+impl CBatchScoringOptions{
+    pub fn set_top_k(&mut self, top_k: usize) -> &mut CBatchScoringOptions {
+        self.top_k = Some(top_k);
+        self
+    }
+    pub fn set_threshold_sim(&mut self, threshold_sim: f64) -> &mut CBatchScoringOptions {
+        self.threshold_sim = Some(threshold_sim);
+        self
+    }
+    pub fn set_with_detail(&mut self, b_with_detail: bool) -> &mut CBatchScoringOptions {
+        self.b_with_detail = b_with_detail;
+        self
+    }
+}
+
+// 一对多相似度查询的单条结果
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CSimilarityMatch{
+    pub account_name: String,
+    pub index: usize,                                       // 候选账号在候选集合中的序号
+    pub similarity: f64,
+    pub detail: Option<CAccountNameSimResultDetail>         // 仅当b_with_detail为true时存在
+}
+
+
+/// # 功能
+/// 计算一个查询账号名与全部候选账号的相似度(多线程)，按相似度从高到低排序返回，相似度相同时按账号名排序。
+/// 查询账号只会被解析一次；设置了threshold_sim时会先使用相似度上界排除不可能达到阈值的候选。
+///
+/// # Function
+/// Score one query account name against all candidates in parallel, and return the results sorted by similarity from high to low (ties are sorted by account name).
+/// The query is analysed only once; when threshold_sim is set, candidates that cannot reach it are skipped using the similarity upper bound.
+pub fn find_similar_accounts_in_slice(query: &CAccountNameSimAnalyse, candidates: &[CAccountNameSimAnalyse], options: &CBatchScoringOptions) -> Vec<CSimilarityMatch>{
+    find_similar_accounts_by_index(query, candidates.len(), |index| &candidates[index], options)
+}
+
+// 按序号取候选账号的一对多相似度查询
+// 打分阶段只保留(序号, 相似度, 细节)，设置了top_k时先用select_nth_unstable_by选出前k个再排序，最后只为返回的结果构建账号名字符串
+fn find_similar_accounts_by_index<'c, C, F>(query: &CAccountNameSimAnalyse, candidate_amount: usize, candidate_of: F, options: &CBatchScoringOptions) -> Vec<CSimilarityMatch>
+    where C: Borrow<CAccountNameSimAnalyse<'c>>, F: Fn(usize) -> C + Sync{
+    let mut scored: Vec<(usize, f64, Option<CAccountNameSimResultDetail>)> = (0..candidate_amount).into_par_iter()
+        .filter_map(|index| {
+            let candidate = candidate_of(index);
+            let (similarity, detail) = match options.threshold_sim {
+                Some(threshold_sim) => query.calc_similarity_at_least(candidate.borrow(), threshold_sim)?,
+                None => query.calc_similarity(candidate.borrow())
+            };
+            Some((index, similarity, if options.b_with_detail { Some(detail) } else { None }))
+        })
+        .collect();
+
+    // 相似度从高到低，其次按账号名，最后按序号，保证结果与线程调度无关
+    let cmp = |a: &(usize, f64, Option<CAccountNameSimResultDetail>), b: &(usize, f64, Option<CAccountNameSimResultDetail>)|
+        b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal)
+            .then_with(|| candidate_of(a.0).borrow().account_name.cmp(candidate_of(b.0).borrow().account_name))
+            .then_with(|| a.0.cmp(&b.0));
+    if let Some(top_k) = options.top_k{
+        if top_k < scored.len(){
+            scored.select_nth_unstable_by(top_k, cmp);
+            scored.truncate(top_k);
+        }
+    }
+    scored.par_sort_by(cmp);
+
+    scored.into_iter()
+        .map(|(index, similarity, detail)| CSimilarityMatch{
+            account_name: candidate_of(index).borrow().account_name.to_string(),
+            index,
+            similarity,
+            detail
+        })
+        .collect()
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    /// 查询与指定账号名相似的账号，结果中的index为账号在to_vec()结果中的序号
+    ///
+    /// Find the accounts similar to the given account name, the index in the results is the position of the account in to_vec()
+    pub fn find_similar_accounts(&self, query: &str, options: &CBatchScoringOptions) -> Vec<CSimilarityMatch>{
+        find_similar_accounts_by_index(&CAccountNameSimAnalyse::new(query), self.data_vec_size, |index| self.analyse_obj(index), options)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn it_works() {
+        let account_vec = vec!["ubut2739", "ubut1179", "ubut2222", "zz2871369", "htgt4303", "33xwb656", "ubut27390"];
+        let ana = CAccountNameAnaVec::new(&account_vec);
+
+        let all = ana.find_similar_accounts("ubut2738", &CBatchScoringOptions::default());
+        assert_eq!(all.len(), account_vec.len());
+        assert_eq!(all[0].account_name, "ubut2739");
+        assert!(all.windows(2).all(|w| w[0].similarity >= w[1].similarity));
+        assert!(all.iter().all(|m| m.detail.is_none() && ana.to_vec()[m.index] == m.account_name));
+
+        let top = ana.find_similar_accounts("ubut2738", CBatchScoringOptions::default().set_top_k(2).set_with_detail(true));
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].similarity, all[0].similarity);
+        assert_eq!(top[1].detail.as_ref().unwrap().sim_total_score, all[1].similarity);
+
+        // 前k个结果与完整排序结果的前k个一致，k不小于结果数量时返回全部结果
+        for top_k in 0..=account_vec.len() + 1{
+            let top = ana.find_similar_accounts("ubut2738", CBatchScoringOptions::default().set_top_k(top_k));
+            assert_eq!(top.iter().map(|m| m.index).collect::<Vec<usize>>(), all.iter().take(top_k).map(|m| m.index).collect::<Vec<usize>>());
+        }
+        let analyses = ana.analyses();
+        let in_slice = find_similar_accounts_in_slice(&CAccountNameSimAnalyse::new("ubut2738"), &analyses, CBatchScoringOptions::default().set_top_k(3));
+        assert_eq!(in_slice.iter().map(|m| (m.index, m.account_name.as_str())).collect::<Vec<(usize, &str)>>(),
+                   all.iter().take(3).map(|m| (m.index, m.account_name.as_str())).collect::<Vec<(usize, &str)>>());
+
+        let above = ana.find_similar_accounts("ubut2738", CBatchScoringOptions::default().set_threshold_sim(0.8));
+        assert_eq!(above.len(), all.iter().filter(|m| m.similarity >= 0.8).count());
+        for m in above.iter(){
+            println!("{:?}", m);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{BTreeMap, HashMap};
+use rayon::prelude::*;
+use crate::analyze_account_name_similarity::CAccountNameSimAnalyse;
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+
+
+/// 分块键: 决定组长模式中哪些账号会被分入同一数据块进行比较
+///
+/// Blocking key: decides which accounts are put into the same block and compared in the leader strategy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlockingKey {
+    #[default]
+    Skeleton,               // 骨架: 零件类型及各零件的字符数量均相同，例如ubut2739与ubut1179
+    LetterStem,             // 字母词干: 第一段非数字字符(小写)，例如ubut9、ubut2739、ubut10644、ubut2739a均为ubut
+    MaskedTemplate,         // 掩码模板: 每段数字替换为#后的账号名(小写)，例如ubut9与ubut10644均为ubut#
+    SortedNeighborhood{     // 排序邻域: 按账号名(小写)排序，排序后相距小于window的账号视为同一块；
+        window: usize       // 在组长模式中作为第一个键时，先按骨架划分数据块，再按窗口进行一轮组间合并
+    }
+}
+impl BlockingKey {
+    // 账号的分块键值，排序邻域的键值为排序用的小写账号名
+    fn key_of(&self, obj: &CAccountNameSimAnalyse) -> String{
+        match self {
+            BlockingKey::Skeleton => format!("{:?}{:?}", obj.skeleton_style, obj.skeleton_part_size_list),
+            BlockingKey::LetterStem => obj.account_name.chars()
+                .skip_while(|c| c.is_ascii_digit())
+                .take_while(|c| !c.is_ascii_digit())
+                .flat_map(char::to_lowercase)
+                .collect(),
+            BlockingKey::MaskedTemplate => {
+                let mut template = String::with_capacity(obj.account_name.len());
+                for c in obj.account_name.chars(){
+                    if !c.is_ascii_digit(){
+                        template.extend(c.to_lowercase());
+                    }else if !template.ends_with('#'){
+                        template.push('#');
+                    }
+                }
+                template
+            },
+            BlockingKey::SortedNeighborhood{..} => obj.account_name.to_lowercase()
+        }
+    }
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    // 按分块键将账号序号划分为数据块，块内保持原有顺序，块之间按块内账号数量降序(相同时保持原有顺序)
+    // 骨架沿用初始化时的排序，仅将相邻且骨架相同的账号划为一块
+    // 排序邻域的窗口无法表示为互不重叠的数据块，因此按骨架划分，窗口由随后的组间合并(merge_groups_by_blocking_key)处理
+    pub(crate) fn partition_index_vec_by_blocking_key(&self, index_vec: &[usize], blocking_key: BlockingKey) -> Vec<Vec<usize>>{
+        let mut blocks: Vec<Vec<usize>> = Vec::new();
+        match blocking_key {
+            BlockingKey::Skeleton | BlockingKey::SortedNeighborhood{..} => {
+                for (position, index) in index_vec.iter().enumerate(){
+                    if position > 0 &&
+                        self.analyse_obj(index_vec[position - 1]).skeleton_style == self.analyse_obj(*index).skeleton_style &&
+                        self.analyse_obj(index_vec[position - 1]).skeleton_part_size_list == self.analyse_obj(*index).skeleton_part_size_list
+                    {
+                        blocks.last_mut().unwrap().push(*index);
+                    }
+                    else {
+                        blocks.push(vec![*index]);
+                    }
+                }
+            },
+            BlockingKey::LetterStem | BlockingKey::MaskedTemplate => {
+                let mut block_map: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+                for index in index_vec{
+                    block_map.entry(blocking_key.key_of(&self.analyse_obj(*index))).or_default().push(*index);
+                }
+                blocks = block_map.into_values().collect();
+            }
+        }
+        blocks.sort_by_key(|block| std::cmp::Reverse(block.len()));
+        blocks
+    }
+
+    // 按分块键再进行一轮组间合并，用于多个分块键中第一个之后的各个键
+    // 未出现在分组结果中的账号(快速模式下被舍弃的低频数据)先各自成组；各组按组员数量降序(相同时按组长序号)依次处理，
+    // 若与同一块(排序邻域下为排序后相距小于window)中某个先处理且未被合并的组的组长相似度高于等于阈值，则并入其中第一个这样的组
+    pub(crate) fn merge_groups_by_blocking_key(&self, index_map: HashMap<usize, Vec<usize>>, blocking_key: BlockingKey, threshold_sim: f64) -> HashMap<usize, Vec<usize>>{
+        let mut b_grouped = vec![false; self.data_vec_size];
+        for index in index_map.values().flatten(){
+            b_grouped[*index] = true;
+        }
+        let mut groups: Vec<(usize, Vec<usize>)> = index_map.into_iter().collect();
+        groups.extend((0..self.data_vec_size).filter(|index| !b_grouped[*index]).map(|index| (index, vec![index])));
+        groups.sort_by_key(|(leader, members)| (std::cmp::Reverse(members.len()), *leader));
+
+        // 各组所在的块、块内按处理顺序的位置排列的组，以及各组在块内的排名；排序邻域下只有一个块，按键值排序
+        let keys: Vec<String> = groups.par_iter().map(|(leader, _)| blocking_key.key_of(&self.analyse_obj(*leader))).collect();
+        let mut block_of = vec![0; groups.len()];
+        let mut rank_of = vec![0; groups.len()];
+        let blocks: Vec<Vec<usize>> = match blocking_key {
+            BlockingKey::SortedNeighborhood{..} => {
+                let mut sorted_positions: Vec<usize> = (0..groups.len()).collect();
+                sorted_positions.sort_by(|a, b| keys[*a].cmp(&keys[*b]).then_with(|| groups[*a].0.cmp(&groups[*b].0)));
+                vec![sorted_positions]
+            },
+            _ => {
+                let mut block_map: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+                for (position, key) in keys.iter().enumerate(){
+                    block_map.entry(key.as_str()).or_default().push(position);
+                }
+                block_map.into_values().collect()
+            }
+        };
+        for (block_index, block) in blocks.iter().enumerate(){
+            for (rank, position) in block.iter().enumerate(){
+                block_of[*position] = block_index;
+                rank_of[*position] = rank;
+            }
+        }
+
+        // 每个组只与同一块中先处理的组比较，逐组生成候选，不保存块内的全部组对
+        let similar_earlier: Vec<Vec<usize>> = (0..groups.len()).into_par_iter()
+            .map(|later| {
+                let block = &blocks[block_of[later]];
+                let rank = rank_of[later];
+                let window_range = match blocking_key {
+                    BlockingKey::SortedNeighborhood{ window } => rank.saturating_sub(window.saturating_sub(1))..block.len().min(rank + window.max(1)),
+                    _ => 0..rank
+                };
+                let mut earlier_list: Vec<usize> = block[window_range].iter().copied()
+                    .filter(|earlier| *earlier < later &&
+                        self.analyse_obj(groups[*earlier].0).calc_similarity_at_least(&self.analyse_obj(groups[later].0), threshold_sim).is_some())
+                    .collect();
+                earlier_list.sort_unstable();
+                earlier_list
+            })
+            .collect();
+
+        // 依次决定各组并入的组
+        let mut owners: Vec<usize> = Vec::with_capacity(groups.len());
+        for (position, earlier_list) in similar_earlier.iter().enumerate(){
+            let owner = earlier_list.iter().copied().find(|earlier| owners[*earlier] == *earlier).unwrap_or(position);
+            owners.push(owner);
+        }
+        let mut merged: Vec<Vec<usize>> = vec![Vec::new(); groups.len()];
+        for (position, (_, members)) in groups.iter_mut().enumerate(){
+            merged[owners[position]].append(members);
+        }
+        groups.iter().zip(merged)
+            .filter(|(_, members)| !members.is_empty())
+            .map(|((leader, _), members)| (*leader, members))
+            .collect()
+    }
+
+    // 为每个查询账号找出至少在一个分块键下与之处于同一块的目标账号(不含查询账号自身)，按序号升序，用于精修时限定组长的比较范围
+    // 排序邻域下按(键值, 序号)排序目标账号，以查询账号的排名为中心，两侧相距小于window的目标账号视为同一块
+    pub(crate) fn find_blocked_candidates(&self, targets: &[usize], queries: &[usize], blocking_keys: &[BlockingKey]) -> Vec<Vec<usize>>{
+        // 各分块键下目标账号的(键值, 序号)，按键值排序
+        let sorted_targets: Vec<Vec<(String, usize)>> = blocking_keys.iter().map(|blocking_key| {
+            let mut keyed: Vec<(String, usize)> = targets.par_iter().map(|target| (blocking_key.key_of(&self.analyse_obj(*target)), *target)).collect();
+            keyed.par_sort_unstable();
+            keyed
+        }).collect();
+
+        queries.par_iter()
+            .map(|query| {
+                let mut candidates: Vec<usize> = Vec::new();
+                for (blocking_key, keyed) in blocking_keys.iter().zip(sorted_targets.iter()){
+                    let key = blocking_key.key_of(&self.analyse_obj(*query));
+                    match blocking_key {
+                        BlockingKey::SortedNeighborhood{ window } => {
+                            // 查询账号在目标账号中的排名(不在其中时为插入位置)，两侧各取相距小于window的目标账号
+                            let radius = window.saturating_sub(1);
+                            let rank = keyed.partition_point(|(target_key, target)| (target_key, target) < (&key, query));
+                            let b_query_in_targets = keyed.get(rank).is_some_and(|(_, target)| target == query);
+                            let end = keyed.len().min(rank.saturating_add(radius).saturating_add(b_query_in_targets as usize));
+                            candidates.extend(keyed[rank.saturating_sub(radius)..end].iter().map(|(_, target)| *target));
+                        },
+                        _ => {
+                            let start = keyed.partition_point(|(target_key, _)| *target_key < key);
+                            candidates.extend(keyed[start..].iter().take_while(|(target_key, _)| *target_key == key).map(|(_, target)| *target));
+                        }
+                    }
+                }
+                candidates.retain(|target| target != query);
+                candidates.sort_unstable();
+                candidates.dedup();
+                candidates
+            })
+            .collect()
+    }
+
+    // 多线程查找相似度高于等于阈值的账号对(i < j)，按(i, j)排序，用于连通分量、密度聚类与社区发现
+    // 只比较至少在一个分块键下处于同一块(排序邻域下为排序后相距小于window)的账号对；分块键为空时比较全部账号对
+    pub(crate) fn find_similar_index_pairs_by_blocking_keys(&self, threshold_sim: f64, blocking_keys: &[BlockingKey]) -> Vec<(usize, usize, f64)>{
+        if blocking_keys.is_empty(){
+            return self.find_similar_index_pairs(threshold_sim);
+        }
+        // 各分块键下: 每个账号所在的块，以及块内按序号排列的账号；排序邻域下为按键值排序后的账号及各账号的排名
+        let partitions: Vec<(Vec<usize>, Vec<Vec<usize>>)> = blocking_keys.iter().map(|blocking_key| {
+            let keys: Vec<String> = (0..self.data_vec_size).into_par_iter().map(|index| blocking_key.key_of(&self.analyse_obj(index))).collect();
+            let mut block_of = vec![0; self.data_vec_size];
+            match blocking_key {
+                BlockingKey::SortedNeighborhood{..} => {
+                    let mut sorted_indexes: Vec<usize> = (0..self.data_vec_size).collect();
+                    sorted_indexes.sort_by(|a, b| keys[*a].cmp(&keys[*b]).then_with(|| a.cmp(b)));
+                    for (rank, index) in sorted_indexes.iter().enumerate(){
+                        block_of[*index] = rank;
+                    }
+                    (block_of, vec![sorted_indexes])
+                },
+                _ => {
+                    let mut block_map: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+                    for (index, key) in keys.iter().enumerate(){
+                        block_map.entry(key.as_str()).or_default().push(index);
+                    }
+                    let blocks: Vec<Vec<usize>> = block_map.into_values().collect();
+                    for (block_index, block) in blocks.iter().enumerate(){
+                        for index in block{
+                            block_of[*index] = block_index;
+                        }
+                    }
+                    (block_of, blocks)
+                }
+            }
+        }).collect();
+
+        (0..self.data_vec_size).into_par_iter()
+            .flat_map_iter(|row| {
+                let mut candidates: Vec<usize> = Vec::new();
+                for (blocking_key, (block_of, blocks)) in blocking_keys.iter().zip(partitions.iter()){
+                    match blocking_key {
+                        BlockingKey::SortedNeighborhood{ window } => {
+                            let rank = block_of[row];
+                            let sorted_indexes = &blocks[0];
+                            let window_range = rank.saturating_sub(window.saturating_sub(1))..sorted_indexes.len().min(rank + window.max(&1));
+                            candidates.extend(sorted_indexes[window_range].iter().filter(|col| **col > row));
+                        },
+                        _ => {
+                            let block = &blocks[block_of[row]];
+                            let start = block.partition_point(|col| *col <= row);
+                            candidates.extend_from_slice(&block[start..]);
+                        }
+                    }
+                }
+                candidates.sort_unstable();
+                candidates.dedup();
+                candidates.into_iter()
+                    .filter_map(move |col| self.analyse_obj(row).calc_similarity_at_least(&self.analyse_obj(col), threshold_sim)
+                        .map(|(similarity, _)| (row, col, similarity)))
+            })
+            .collect()
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_account_name_by_similarity::{CSimilarityGroupingThreshold, CGroupingOptions, EfficiencyMode, ClusteringStrategy};
+    #[test]
+    fn it_works() {
+        let obj = CAccountNameSimAnalyse::new("12UBut2739a8");
+        assert_eq!(BlockingKey::LetterStem.key_of(&obj), "ubut");
+        assert_eq!(BlockingKey::MaskedTemplate.key_of(&obj), "#ubut#a#");
+        assert_eq!(BlockingKey::SortedNeighborhood{ window: 3 }.key_of(&obj), "12ubut2739a8");
+
+        let account_list = std::fs::read_to_string("test_data/test_account_list.txt").unwrap();
+        let mut account_vec: Vec<&str> = account_list.lines().take(300).collect();
+        account_vec.push("ubut2068a");
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.85, threshold_group_members: 2 };
+        let mut options = CGroupingOptions::default().set_mode(EfficiencyMode::Rapidly).clone();
+        let group_of = |options: &CGroupingOptions, name: &str| ana.group_by_similarity_with_options(&threshold, options).groups.into_iter()
+            .find(|g| g.member_names().contains(&name))
+            .map(|g| g.member_names().iter().map(|name| name.to_string()).collect::<Vec<String>>());
+
+        // 按骨架分块时ubut2068a单独处于一个数据块，快速模式下作为低频数据被舍弃
+        assert_eq!(group_of(&options, "ubut2068a"), None);
+        for blocking_keys in [vec![BlockingKey::LetterStem],
+                              vec![BlockingKey::SortedNeighborhood{ window: 400 }],
+                              vec![BlockingKey::Skeleton, BlockingKey::MaskedTemplate, BlockingKey::SortedNeighborhood{ window: 5 }]]{
+            options.set_blocking_keys(blocking_keys);
+            let group = group_of(&options, "ubut2068a").unwrap();
+            assert!(group.len() > 2 && group.iter().all(|name| name.starts_with("ubut")));
+            assert_eq!(group_of(&options, "ubut2068"), Some(group));
+        }
+        // 排序邻域作为第一个键时按骨架划分数据块，窗口决定组间合并的范围
+        options.set_blocking_keys(vec![BlockingKey::SortedNeighborhood{ window: 1 }]);
+        assert_eq!(group_of(&options, "ubut2068a"), None);
+        for window in [5, 400]{
+            options.set_blocking_keys(vec![BlockingKey::SortedNeighborhood{ window }]);
+            let snm_first = ana.group_by_similarity_with_options(&threshold, &options);
+            options.set_blocking_keys(vec![BlockingKey::Skeleton, BlockingKey::SortedNeighborhood{ window }]);
+            assert_eq!(snm_first.groups, ana.group_by_similarity_with_options(&threshold, &options).groups);
+        }
+
+        // 合并后每个账号恰好属于一个组
+        let index_map = ana.group_account_indexes_by_similarity(&mut threshold.clone(), CGroupingOptions::default().set_mode(EfficiencyMode::Rapidly));
+        let merged = ana.merge_groups_by_blocking_key(index_map.clone(), BlockingKey::LetterStem, 0.85);
+        let mut indexes: Vec<usize> = merged.iter().flat_map(|(leader, members)| { assert_eq!(members[0], *leader); members.clone() }).collect();
+        indexes.sort_unstable();
+        assert_eq!(indexes, (0..ana.to_vec().len()).collect::<Vec<usize>>());
+        assert!(merged.len() < index_map.len() + ana.to_vec().len() - index_map.values().map(|members| members.len()).sum::<usize>());
+
+        // 分块后的相似账号对: 恰好为全部相似账号对中处于同一块的部分；排序邻域的窗口覆盖全部账号时与不分块一致
+        let exhaustive = ana.find_similar_index_pairs(0.85);
+        assert_eq!(ana.find_similar_index_pairs_by_blocking_keys(0.85, &[]), exhaustive);
+        let objs = ana.analyses();
+        let same_stem = |(a, b, _): &&(usize, usize, f64)| BlockingKey::LetterStem.key_of(&objs[*a]) == BlockingKey::LetterStem.key_of(&objs[*b]);
+        let same_skeleton = |(a, b, _): &&(usize, usize, f64)| BlockingKey::Skeleton.key_of(&objs[*a]) == BlockingKey::Skeleton.key_of(&objs[*b]);
+        let blocked = ana.find_similar_index_pairs_by_blocking_keys(0.85, &[BlockingKey::LetterStem]);
+        assert_eq!(blocked, exhaustive.iter().filter(same_stem).copied().collect::<Vec<_>>());
+        assert!(blocked.len() < exhaustive.len());
+        assert_eq!(ana.find_similar_index_pairs_by_blocking_keys(0.85, &[BlockingKey::LetterStem, BlockingKey::Skeleton]),
+                   exhaustive.iter().filter(|pair| same_stem(pair) || same_skeleton(pair)).copied().collect::<Vec<_>>());
+        assert_eq!(ana.find_similar_index_pairs_by_blocking_keys(0.85, &[BlockingKey::SortedNeighborhood{ window: ana.to_vec().len() }]), exhaustive);
+        let neighbours = ana.find_similar_index_pairs_by_blocking_keys(0.85, &[BlockingKey::SortedNeighborhood{ window: 5 }]);
+        assert!(neighbours.iter().all(|pair| exhaustive.contains(pair)) && neighbours.len() < exhaustive.len());
+
+        // 精修的候选组长: 至少在一个分块键下与查询账号处于同一块的目标账号
+        let targets: Vec<usize> = (0..objs.len()).step_by(3).collect();
+        let queries: Vec<usize> = (0..objs.len()).collect();
+        let stem_candidates = ana.find_blocked_candidates(&targets, &queries, &[BlockingKey::LetterStem]);
+        for (query, candidates) in queries.iter().zip(stem_candidates.iter()){
+            let same_stem_targets: Vec<usize> = targets.iter().copied()
+                .filter(|target| target != query && BlockingKey::LetterStem.key_of(&objs[*target]) == BlockingKey::LetterStem.key_of(&objs[*query]))
+                .collect();
+            assert_eq!(candidates, &same_stem_targets);
+        }
+        assert!(ana.find_blocked_candidates(&targets, &queries, &[BlockingKey::SortedNeighborhood{ window: 1 }]).iter().all(|candidates| candidates.is_empty()));
+        let wide_candidates = ana.find_blocked_candidates(&targets, &queries, &[BlockingKey::SortedNeighborhood{ window: 2 * objs.len() }]);
+        assert!(queries.iter().zip(wide_candidates.iter()).all(|(query, candidates)| candidates.len() == targets.len() - targets.contains(query) as usize));
+        // 窗口边界: window为2时只取排序后紧邻的账号，查询账号不在目标账号中时取插入位置两侧各一个
+        let boundary_names = vec!["aa", "ab", "ac", "ad", "ae", "ac1"];
+        let boundary = CAccountNameAnaVec::new(&boundary_names);
+        let boundary_vec = boundary.to_vec();
+        let index_of = |name: &str| boundary_vec.iter().position(|n| n == name).unwrap();
+        let names_of = |candidates: &Vec<usize>| { let mut names: Vec<&str> = candidates.iter().map(|i| boundary_vec[*i].as_str()).collect(); names.sort_unstable(); names };
+        let boundary_targets: Vec<usize> = ["aa", "ab", "ac", "ad", "ae"].iter().map(|name| index_of(name)).collect();
+        let boundary_queries: Vec<usize> = ["aa", "ab", "ac", "ae", "ac1"].iter().map(|name| index_of(name)).collect();
+        let boundary_candidates = boundary.find_blocked_candidates(&boundary_targets, &boundary_queries, &[BlockingKey::SortedNeighborhood{ window: 2 }]);
+        assert_eq!(boundary_candidates.iter().map(names_of).collect::<Vec<_>>(),
+                   vec![vec!["ab"], vec!["aa", "ac"], vec!["ab", "ad"], vec!["ad"], vec!["ac", "ad"]]);
+
+        // 连通分量使用分块键查找相似账号对
+        let components_options = CGroupingOptions::default().set_strategy(ClusteringStrategy::ConnectedComponents).set_blocking_keys(vec![BlockingKey::LetterStem]).clone();
+        let components = ana.group_by_similarity_with_options(&CSimilarityGroupingThreshold{ threshold_sim: 0.85, threshold_group_members: 1 }, &components_options);
+        let names = ana.to_vec();
+        assert!(components.groups.iter().all(|g| g.member_names().iter()
+            .all(|name| BlockingKey::LetterStem.key_of(&CAccountNameSimAnalyse::new(name)) == BlockingKey::LetterStem.key_of(&CAccountNameSimAnalyse::new(&g.leader)))));
+        assert_eq!(components.groups.iter().map(|g| g.size()).sum::<usize>(), names.len());
+    }
+}
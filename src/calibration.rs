@@ -0,0 +1,432 @@
+use std::cmp::Ordering;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use crate::analyze_account_name_similarity::{CAccountNameSimAnalyse, CAccountNameSimAnalyseParamsWeightTable, CAccountNameSimResultDetail, DEFAULT_PARAMETER_WEIGHT_TABLE};
+use crate::score_combiner::{ScoreCombiner, CEditDistanceJaroCombiner};
+
+
+// 逻辑回归训练时的迭代次数及L2正则系数
+const PLATT_MAX_ITERATIONS: usize = 100;
+const PLATT_L2_REGULARIZATION: f64 = 1e-6;
+
+
+// 校准方式
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CalibrationMethod {
+    PlattOnTotalScore = 0,      // 仅对sim_total_score做Platt(逻辑回归)校准
+    PlattOnDetailVector = 1,    // 对相似度细节中的全部分量做Platt(逻辑回归)校准
+    Isotonic = 2                // 对sim_total_score做保序回归校准
+}
+
+// 校准模型，序列化时以method字段区分
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "method", rename_all = "snake_case"))]
+enum CalibrationModel {
+    Platt { b_detail_vector: bool, feature_mean: Vec<f64>, feature_scale: Vec<f64>, coefficients: Vec<f64>, intercept: f64 },
+    Isotonic { scores: Vec<f64>, probabilities: Vec<f64> }
+}
+
+/// # Description
+/// * CSimilarityCalibrator会将相似度分数映射为"两个账号属于同一组"的概率。
+/// * CSimilarityCalibrator maps a raw similarity score to the probability that two accounts belong to the same group.
+/// # Function
+/// * 使用带标注的账号名对(是否同组)拟合校准模型，支持Platt校准与保序回归校准，模型可通过to_json/from_json保存为JSON。
+/// * Fit a calibration model on labeled account name pairs (same group or not). Platt scaling and isotonic regression are supported, and the model can be saved as JSON through to_json/from_json.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CSimilarityCalibrator{
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    model: CalibrationModel
+}
+impl CSimilarityCalibrator{
+
+    /// 使用带标注的账号名对拟合校准模型，样本中必须同时包含正负样本，否则返回None。相似度按默认的权重表及分数组合器计算
+    ///
+    /// Fit a calibration model on labeled account name pairs. Returns None unless both positive and negative pairs are present.
+    /// The similarity is computed with the default weight table and score combiner
+    pub fn fit(labeled_pairs: &[(&str, &str, bool)], method: CalibrationMethod) -> Option<CSimilarityCalibrator>{
+        CSimilarityCalibrator::fit_by_specify_combiner(labeled_pairs, &DEFAULT_PARAMETER_WEIGHT_TABLE, &CEditDistanceJaroCombiner, method)
+    }
+
+    /// 使用带标注的账号名对拟合校准模型，相似度按指定的权重表及分数组合器计算。
+    /// 校准时需使用同一权重表及分数组合器，即calc_calibrated_similarity_by_specify_combiner
+    ///
+    /// Fit a calibration model on labeled account name pairs, with the similarity computed by the given weight table and score combiner.
+    /// Calibrate with the same weight table and score combiner, i.e. through calc_calibrated_similarity_by_specify_combiner
+    pub fn fit_by_specify_combiner(labeled_pairs: &[(&str, &str, bool)],
+                                   weight_table: &CAccountNameSimAnalyseParamsWeightTable,
+                                   combiner: &dyn ScoreCombiner,
+                                   method: CalibrationMethod) -> Option<CSimilarityCalibrator>{
+        let samples: Vec<(CAccountNameSimResultDetail, bool)> = labeled_pairs.par_iter()
+            .map(|(name_a, name_b, b_same_group)|
+                (CAccountNameSimAnalyse::new(name_a).calc_similarity_by_specify_combiner(&CAccountNameSimAnalyse::new(name_b), weight_table, combiner).1, *b_same_group))
+            .collect();
+        CSimilarityCalibrator::fit_by_result_details(&samples, method)
+    }
+
+    /// 使用已计算好的相似度细节拟合校准模型(例如使用了自定义权重表的计算结果)
+    ///
+    /// Fit a calibration model on already computed similarity details (e.g. computed with a custom weight table)
+    pub fn fit_by_result_details(samples: &[(CAccountNameSimResultDetail, bool)], method: CalibrationMethod) -> Option<CSimilarityCalibrator>{
+        let positive_amount = samples.iter().filter(|s| s.1).count();
+        if positive_amount == 0 || positive_amount == samples.len(){
+            return None;
+        }
+
+        let model = match method {
+            CalibrationMethod::PlattOnTotalScore => {
+                let features = samples.iter().map(|s| vec![s.0.sim_total_score]).collect::<Vec<Vec<f64>>>();
+                fit_platt(&features, samples, false)
+            },
+            CalibrationMethod::PlattOnDetailVector => {
+                let features = samples.iter().map(|s| s.0.to_feature_vec()).collect::<Vec<Vec<f64>>>();
+                fit_platt(&features, samples, true)
+            },
+            CalibrationMethod::Isotonic => {
+                fit_isotonic(&samples.iter().map(|s| (s.0.sim_total_score, s.1)).collect::<Vec<(f64, bool)>>())
+            }
+        };
+        Some(CSimilarityCalibrator{ model })
+    }
+
+    /// 返回校准后的概率
+    ///
+    /// Return the calibrated probability
+    pub fn calibrate(&self, detail: &CAccountNameSimResultDetail) -> f64{
+        match &self.model {
+            CalibrationModel::Platt { b_detail_vector, feature_mean, feature_scale, coefficients, intercept } => {
+                let features = if *b_detail_vector { detail.to_feature_vec() } else { vec![detail.sim_total_score] };
+                let mut z = *intercept;
+                for index in 0..coefficients.len(){
+                    z += coefficients[index] * (features[index] - feature_mean[index]) / feature_scale[index];
+                }
+                sigmoid(z)
+            },
+            CalibrationModel::Isotonic { scores, probabilities } => {
+                interpolate_step_points(scores, probabilities, detail.sim_total_score)
+            }
+        }
+    }
+}
+
+// JSON格式与启用serde特性时的序列化结果一致: 以method字段区分校准模型，其余字段与模型的字段同名
+impl CSimilarityCalibrator{
+
+    /// 序列化为JSON字符串
+    ///
+    /// Serialize to a JSON string
+    pub fn to_json(&self) -> String{
+        let value = match &self.model {
+            CalibrationModel::Platt { b_detail_vector, feature_mean, feature_scale, coefficients, intercept } => json!({
+                "method": "platt",
+                "b_detail_vector": b_detail_vector,
+                "feature_mean": feature_mean,
+                "feature_scale": feature_scale,
+                "coefficients": coefficients,
+                "intercept": intercept
+            }),
+            CalibrationModel::Isotonic { scores, probabilities } => json!({
+                "method": "isotonic",
+                "scores": scores,
+                "probabilities": probabilities
+            })
+        };
+        value.to_string()
+    }
+
+    /// 从JSON字符串中加载并校验，格式错误或模型无效(例如标准化系数为0)时返回None
+    ///
+    /// Load from a JSON string and validate it, returns None if the format is invalid or the model is unusable (e.g. a zero feature scale)
+    pub fn from_json(json_str: &str) -> Option<CSimilarityCalibrator>{
+        let value: Value = serde_json::from_str(json_str).ok()?;
+        let f64_vec = |name: &str| value.get(name)?.as_array()?.iter().map(|item| item.as_f64()).collect::<Option<Vec<f64>>>();
+        let model = match value.get("method")?.as_str()? {
+            "platt" => CalibrationModel::Platt {
+                b_detail_vector: value.get("b_detail_vector")?.as_bool()?,
+                feature_mean: f64_vec("feature_mean")?,
+                feature_scale: f64_vec("feature_scale")?,
+                coefficients: f64_vec("coefficients")?,
+                intercept: value.get("intercept")?.as_f64()?
+            },
+            "isotonic" => CalibrationModel::Isotonic { scores: f64_vec("scores")?, probabilities: f64_vec("probabilities")? },
+            _ => return None
+        };
+        let calibrator = CSimilarityCalibrator{ model };
+        if calibrator.validate() { Some(calibrator) } else { None }
+    }
+
+    // 校验模型: 各向量的长度与特征数量一致、全部数值为有限数、标准化系数大于0，保序回归的插值节点非空且分数单调不减
+    fn validate(&self) -> bool{
+        let b_finite = |values: &[f64]| values.iter().all(|value| value.is_finite());
+        match &self.model {
+            CalibrationModel::Platt { b_detail_vector, feature_mean, feature_scale, coefficients, intercept } => {
+                let feature_len = if *b_detail_vector { CAccountNameSimResultDetail::default().to_feature_vec().len() } else { 1 };
+                feature_mean.len() == feature_len && feature_scale.len() == feature_len && coefficients.len() == feature_len &&
+                    b_finite(feature_mean) && b_finite(coefficients) && intercept.is_finite() &&
+                    feature_scale.iter().all(|scale| scale.is_finite() && *scale > 0.0)
+            },
+            CalibrationModel::Isotonic { scores, probabilities } => {
+                !scores.is_empty() && scores.len() == probabilities.len() && b_finite(scores) && b_finite(probabilities) &&
+                    scores.windows(2).all(|pair| pair[0] <= pair[1])
+            }
+        }
+    }
+}
+
+/// 在带标注的(相似度分数, 是否同组)样本上寻找满足目标精确率的最低阈值，不存在满足条件的阈值时返回None
+///
+/// 返回值可直接作为CSimilarityGroupingThreshold::threshold_sim使用
+///
+/// Find the lowest threshold that reaches the target precision on labeled (score, same group) samples, returns None if no threshold qualifies.
+/// The result can be used directly as CSimilarityGroupingThreshold::threshold_sim
+pub fn calc_threshold_by_precision(samples: &[(f64, bool)], target_precision: f64) -> Option<f64>{
+    let mut sorted_samples = samples.to_vec();
+    sorted_samples.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    let mut result = None;
+    let mut true_positive = 0;
+    let mut index = 0;
+    while index < sorted_samples.len(){
+        // 分数相同的样本要么同时高于阈值，要么同时低于阈值
+        let score = sorted_samples[index].0;
+        while index < sorted_samples.len() && sorted_samples[index].0 == score{
+            if sorted_samples[index].1{
+                true_positive += 1;
+            }
+            index += 1;
+        }
+        if true_positive as f64 / index as f64 >= target_precision{
+            result = Some(score);
+        }
+    }
+    result
+}
+
+
+fn sigmoid(z: f64) -> f64{
+    1.0 / (1.0 + (-z).exp())
+}
+
+// 分段线性插值，超出范围时取端点值
+fn interpolate_step_points(xs: &[f64], ys: &[f64], x: f64) -> f64{
+    if x <= xs[0]{
+        return ys[0];
+    }
+    for index in 1..xs.len(){
+        if x <= xs[index]{
+            let ratio = (x - xs[index - 1]) / (xs[index] - xs[index - 1]);
+            return ys[index - 1] + ratio * (ys[index] - ys[index - 1]);
+        }
+    }
+    ys[ys.len() - 1]
+}
+
+// Platt校准: 对标准化后的特征做逻辑回归，使用Platt平滑后的目标值，以牛顿法求解
+fn fit_platt(features: &[Vec<f64>], samples: &[(CAccountNameSimResultDetail, bool)], b_detail_vector: bool) -> CalibrationModel{
+    let sample_amount = features.len();
+    let feature_len = features[0].len();
+
+    // 特征标准化
+    let mut feature_mean = vec![0.0; feature_len];
+    let mut feature_scale = vec![0.0; feature_len];
+    for feature in features{
+        for index in 0..feature_len{
+            feature_mean[index] += feature[index] / sample_amount as f64;
+        }
+    }
+    for feature in features{
+        for index in 0..feature_len{
+            feature_scale[index] += (feature[index] - feature_mean[index]).powi(2) / sample_amount as f64;
+        }
+    }
+    for scale in feature_scale.iter_mut(){
+        *scale = if *scale > 0.0 { scale.sqrt() } else { 1.0 };
+    }
+    let normalized_features = features.iter()
+        .map(|f| (0..feature_len).map(|i| (f[i] - feature_mean[i]) / feature_scale[i]).collect::<Vec<f64>>())
+        .collect::<Vec<Vec<f64>>>();
+
+    // Platt平滑后的目标值
+    let positive_amount = samples.iter().filter(|s| s.1).count() as f64;
+    let negative_amount = sample_amount as f64 - positive_amount;
+    let target_positive = (positive_amount + 1.0) / (positive_amount + 2.0);
+    let target_negative = 1.0 / (negative_amount + 2.0);
+
+    // params[0]为截距，其后为各特征的系数
+    let dim = feature_len + 1;
+    let mut params = vec![0.0; dim];
+    for _iteration in 0..PLATT_MAX_ITERATIONS{
+        let mut gradient = vec![0.0; dim];
+        let mut hessian = vec![vec![0.0; dim]; dim];
+        for (sample_index, feature) in normalized_features.iter().enumerate(){
+            let mut x = vec![1.0];
+            x.extend_from_slice(feature);
+            let z: f64 = (0..dim).map(|i| params[i] * x[i]).sum();
+            let p = sigmoid(z);
+            let target = if samples[sample_index].1 { target_positive } else { target_negative };
+            let w = (p * (1.0 - p)).max(1e-12);
+            for i in 0..dim{
+                gradient[i] += (p - target) * x[i];
+                for j in 0..dim{
+                    hessian[i][j] += w * x[i] * x[j];
+                }
+            }
+        }
+        for i in 1..dim{
+            gradient[i] += PLATT_L2_REGULARIZATION * params[i];
+            hessian[i][i] += PLATT_L2_REGULARIZATION;
+        }
+
+        let step = match solve_linear_system(hessian, gradient){
+            Some(step) => step,
+            None => break
+        };
+        let mut max_step: f64 = 0.0;
+        for i in 0..dim{
+            params[i] -= step[i];
+            max_step = max_step.max(step[i].abs());
+        }
+        if max_step < 1e-10{
+            break;
+        }
+    }
+
+    CalibrationModel::Platt {
+        b_detail_vector,
+        feature_mean,
+        feature_scale,
+        coefficients: params[1..].to_vec(),
+        intercept: params[0]
+    }
+}
+
+// 高斯消元求解线性方程组，矩阵奇异时返回None
+fn solve_linear_system(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>>{
+    let dim = rhs.len();
+    for col in 0..dim{
+        let pivot = (col..dim).max_by(|a, b| matrix[*a][col].abs().partial_cmp(&matrix[*b][col].abs()).unwrap_or(Ordering::Equal))?;
+        if matrix[pivot][col].abs() < 1e-12{
+            return None;
+        }
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+        let pivot_row = matrix[col].clone();
+        for row in (col + 1)..dim{
+            let factor = matrix[row][col] / pivot_row[col];
+            for k in col..dim{
+                matrix[row][k] -= factor * pivot_row[k];
+            }
+            rhs[row] -= factor * rhs[col];
+        }
+    }
+    let mut result = vec![0.0; dim];
+    for row in (0..dim).rev(){
+        let mut value = rhs[row];
+        for k in (row + 1)..dim{
+            value -= matrix[row][k] * result[k];
+        }
+        result[row] = value / matrix[row][row];
+    }
+    Some(result)
+}
+
+// 保序回归校准: 使用PAV(Pool Adjacent Violators)算法，每个块以(平均分数, 正样本比例)作为插值节点
+// 分数相同的样本先合并为一个块，保证同一分数只对应一个概率，且结果与样本顺序无关
+fn fit_isotonic(samples: &[(f64, bool)]) -> CalibrationModel{
+    let mut sorted_samples = samples.to_vec();
+    sorted_samples.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+    // 块: (分数之和, 正样本数, 样本数)
+    let mut tied_blocks: Vec<(f64, f64, f64)> = Vec::new();
+    let mut last_score = None;
+    for (score, b_same_group) in sorted_samples{
+        let positive = if b_same_group { 1.0 } else { 0.0 };
+        match tied_blocks.last_mut() {
+            Some(block) if last_score == Some(score) => *block = (block.0 + score, block.1 + positive, block.2 + 1.0),
+            _ => tied_blocks.push((score, positive, 1.0))
+        }
+        last_score = Some(score);
+    }
+
+    let mut blocks: Vec<(f64, f64, f64)> = Vec::new();
+    for tied_block in tied_blocks{
+        blocks.push(tied_block);
+        while blocks.len() > 1{
+            let last = blocks[blocks.len() - 1];
+            let prev = blocks[blocks.len() - 2];
+            if prev.1 / prev.2 < last.1 / last.2{
+                break;
+            }
+            blocks.pop();
+            *blocks.last_mut().unwrap() = (prev.0 + last.0, prev.1 + last.1, prev.2 + last.2);
+        }
+    }
+
+    CalibrationModel::Isotonic {
+        scores: blocks.iter().map(|b| b.0 / b.2).collect(),
+        probabilities: blocks.iter().map(|b| b.1 / b.2).collect()
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn it_works() {
+        let labeled_pairs = vec![
+            ("ubut2739", "ubut1179", true), ("ubut2222", "ubut1057", true), ("htgt4303", "htgt4410", true),
+            ("0ubutz22ae22", "2ubutz10ae57", true), ("33xwb656", "33xwb778", true), ("uvgv2965", "uvgv1024", true),
+            ("uvgv2965", "zz2871369", false), ("u0j2e9u1s2h8l91", "t9x1h8y0b7g6f42", false), ("htgt4303", "ubut1179", false),
+            ("a1f6", "b2c", false), ("zz2871369", "33xwb656", false), ("lalala1234lala4t", "ubut2739", false),
+        ];
+        for method in [CalibrationMethod::PlattOnTotalScore, CalibrationMethod::PlattOnDetailVector, CalibrationMethod::Isotonic].iter(){
+            let calibrator = CSimilarityCalibrator::fit(&labeled_pairs, *method).unwrap();
+            let high = CAccountNameSimAnalyse::new("ubut2739").calc_calibrated_similarity(&CAccountNameSimAnalyse::new("ubut2740"), &calibrator);
+            let low = CAccountNameSimAnalyse::new("ubut2739").calc_calibrated_similarity(&CAccountNameSimAnalyse::new("zz2871369"), &calibrator);
+            assert!(0.0 <= low.1 && low.1 < high.1 && high.1 <= 1.0);
+            assert_eq!(CSimilarityCalibrator::from_json(&calibrator.to_json()), Some(calibrator.clone()));
+            // 与serde的序列化格式一致
+            #[cfg(feature = "serde")]
+            {
+                assert_eq!(serde_json::from_str::<CSimilarityCalibrator>(&calibrator.to_json()).unwrap(), calibrator);
+                assert_eq!(CSimilarityCalibrator::from_json(&serde_json::to_string(&calibrator).unwrap()), Some(calibrator));
+            }
+        }
+        assert_eq!(CSimilarityCalibrator::fit(&labeled_pairs[..6], CalibrationMethod::Isotonic), None);
+
+        // 使用自定义权重表及分数组合器拟合的模型，校准时按同一权重表及分数组合器计算相似度
+        let weight_table = crate::analyze_account_name_similarity::UNIFORM_PARAMETER_WEIGHT_TABLE;
+        let combiner = crate::score_combiner::CWeightedMeanCombiner::default();
+        let custom = CSimilarityCalibrator::fit_by_specify_combiner(&labeled_pairs, &weight_table, &combiner, CalibrationMethod::PlattOnDetailVector).unwrap();
+        let (obj_a, obj_b) = (CAccountNameSimAnalyse::new("ubut2739"), CAccountNameSimAnalyse::new("ubut2740"));
+        let (sim, probability, detail) = obj_a.calc_calibrated_similarity_by_specify_combiner(&obj_b, &weight_table, &combiner, &custom);
+        let expected_detail = obj_a.calc_similarity_by_specify_combiner(&obj_b, &weight_table, &combiner).1;
+        assert_eq!((sim, probability), (expected_detail.sim_total_score, custom.calibrate(&expected_detail)));
+        assert_eq!(detail.to_feature_vec(), expected_detail.to_feature_vec());
+        assert_ne!(sim, obj_a.calc_similarity(&obj_b).0);
+
+        // 分数相同而标注不同的样本合并为同一个插值节点，结果与样本顺序无关
+        let tied_detail = CAccountNameSimResultDetail{ sim_total_score: 0.5, ..Default::default() };
+        let tied_samples = vec![(tied_detail.clone(), false), (tied_detail.clone(), true)];
+        let tied = CSimilarityCalibrator::fit_by_result_details(&tied_samples, CalibrationMethod::Isotonic).unwrap();
+        let reversed_samples: Vec<(CAccountNameSimResultDetail, bool)> = tied_samples.iter().rev().cloned().collect();
+        assert_eq!(CSimilarityCalibrator::fit_by_result_details(&reversed_samples, CalibrationMethod::Isotonic), Some(tied.clone()));
+        assert_eq!(tied.calibrate(&tied_detail), 0.5);
+
+        // 无效的模型: 标准化系数为0会得到NaN/inf，保序回归没有插值节点，以及未知的校准方式
+        let zero_scale = CSimilarityCalibrator{ model: CalibrationModel::Platt { b_detail_vector: false, feature_mean: vec![0.5], feature_scale: vec![0.0], coefficients: vec![1.0], intercept: 0.0 } };
+        assert_eq!(CSimilarityCalibrator::from_json(&zero_scale.to_json()), None);
+        assert_eq!(CSimilarityCalibrator::from_json("{\"method\":\"isotonic\",\"scores\":[],\"probabilities\":[]}"), None);
+        assert!(CSimilarityCalibrator::from_json("{\"method\":\"isotonic\",\"scores\":[0.2,0.8],\"probabilities\":[0.1,0.9]}").is_some());
+        assert_eq!(CSimilarityCalibrator::from_json("{\"method\":\"beta\",\"scores\":[0.2,0.8],\"probabilities\":[0.1,0.9]}"), None);
+
+        assert_eq!(calc_threshold_by_precision(&[(0.9, true), (0.8, true), (0.7, false), (0.6, true), (0.5, false)], 0.95), Some(0.8));
+        assert_eq!(calc_threshold_by_precision(&[(0.9, true), (0.8, true), (0.7, false), (0.6, true), (0.5, false)], 0.75), Some(0.6));
+        assert_eq!(calc_threshold_by_precision(&[(0.9, false)], 0.5), None);
+    }
+}
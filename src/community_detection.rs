@@ -0,0 +1,235 @@
+use std::collections::{BTreeMap, HashMap};
+use crate::group_account_name_by_similarity::{CAccountNameAnaVec, CSimilarityGroupingThreshold, CGroupingOptions, ClusteringStrategy};
+use crate::grouping_result::{CClusteringAnnotations, CGroupingResult};
+
+
+// 局部移动阶段中，模块度增益超过该值才移动节点，避免浮点误差导致的反复移动
+const MODULARITY_GAIN_EPSILON: f64 = 1e-12;
+
+// Louvain算法的划分结果
+pub(crate) struct CCommunityPartition{
+    pub(crate) communities: Vec<usize>,             // 各节点所属社区的序号，按首次出现的顺序编号
+    pub(crate) community_modularity: Vec<f64>,      // 各社区对模块度的贡献，之和即为模块度
+    pub(crate) modularity: f64
+}
+
+// 带权无向图，边(u <= v)各出现一次，u == v为自环
+struct CWeightedGraph{
+    node_amount: usize,
+    edges: Vec<(usize, usize, f64)>
+}
+impl CWeightedGraph{
+    // 邻接表(不含自环)以及各节点的度(自环计两次)
+    fn adjacency(&self) -> (Vec<Vec<(usize, f64)>>, Vec<f64>){
+        let mut adjacency: Vec<Vec<(usize, f64)>> = vec![Vec::new(); self.node_amount];
+        let mut degrees = vec![0.0; self.node_amount];
+        for (u, v, weight) in self.edges.iter(){
+            if u == v{
+                degrees[*u] += 2.0 * weight;
+            }else {
+                adjacency[*u].push((*v, *weight));
+                adjacency[*v].push((*u, *weight));
+                degrees[*u] += weight;
+                degrees[*v] += weight;
+            }
+        }
+        (adjacency, degrees)
+    }
+
+    // 局部移动: 按节点序号依次将节点移入模块度增益最大的相邻社区，直到没有节点移动。返回各节点的社区(按首次出现的顺序编号)及是否发生过移动
+    fn move_nodes_locally(&self, total_weight: f64) -> (Vec<usize>, bool){
+        let (adjacency, degrees) = self.adjacency();
+        let mut communities: Vec<usize> = (0..self.node_amount).collect();
+        let mut community_degrees = degrees.clone();
+        let mut b_moved = false;
+        loop {
+            let mut b_moved_this_round = false;
+            for node in 0..self.node_amount{
+                let current = communities[node];
+                community_degrees[current] -= degrees[node];
+                let mut weight_to_community: BTreeMap<usize, f64> = BTreeMap::new();
+                weight_to_community.insert(current, 0.0);
+                for (neighbour, weight) in adjacency[node].iter(){
+                    *(weight_to_community.entry(communities[*neighbour]).or_insert(0.0)) += weight;
+                }
+                let gain = |community: usize, weight: f64| weight - community_degrees[community] * degrees[node] / (2.0 * total_weight);
+                // 增益相同时留在原社区，其次取序号最小的社区
+                let mut best = (current, gain(current, weight_to_community[&current]));
+                for (community, weight) in weight_to_community.iter(){
+                    let community_gain = gain(*community, *weight);
+                    if community_gain > best.1 + MODULARITY_GAIN_EPSILON{
+                        best = (*community, community_gain);
+                    }
+                }
+                community_degrees[best.0] += degrees[node];
+                if best.0 != current{
+                    communities[node] = best.0;
+                    b_moved_this_round = true;
+                    b_moved = true;
+                }
+            }
+            if !b_moved_this_round{
+                break;
+            }
+        }
+        (renumber_communities(&communities), b_moved)
+    }
+
+    // 将同一社区的节点聚合为一个节点
+    fn aggregate(&self, communities: &[usize]) -> CWeightedGraph{
+        let mut edge_map: BTreeMap<(usize, usize), f64> = BTreeMap::new();
+        for (u, v, weight) in self.edges.iter(){
+            let (a, b) = (communities[*u], communities[*v]);
+            *(edge_map.entry((a.min(b), a.max(b))).or_insert(0.0)) += weight;
+        }
+        CWeightedGraph{
+            node_amount: communities.iter().max().map(|max| max + 1).unwrap_or(0),
+            edges: edge_map.into_iter().map(|((u, v), weight)| (u, v, weight)).collect()
+        }
+    }
+}
+
+// 按首次出现的顺序重新编号社区
+fn renumber_communities(communities: &[usize]) -> Vec<usize>{
+    let mut number_table: HashMap<usize, usize> = HashMap::new();
+    communities.iter()
+        .map(|community| {
+            let next_number = number_table.len();
+            *number_table.entry(*community).or_insert(next_number)
+        })
+        .collect()
+}
+
+// Louvain模块度优化: 交替进行局部移动与社区聚合，直到社区不再变化
+// edges为带权无向边(u, v, 权重)，每条边出现一次，权重应为正数
+pub(crate) fn detect_communities(node_amount: usize, edges: &[(usize, usize, f64)]) -> CCommunityPartition{
+    let mut graph = CWeightedGraph{
+        node_amount,
+        edges: edges.iter().map(|(u, v, weight)| (*u.min(v), *u.max(v), *weight)).collect()
+    };
+    let total_weight: f64 = graph.edges.iter().map(|edge| edge.2).sum();
+    let mut communities: Vec<usize> = (0..node_amount).collect();
+    if total_weight > 0.0{
+        loop {
+            let (level_communities, b_moved) = graph.move_nodes_locally(total_weight);
+            if !b_moved{
+                break;
+            }
+            for community in communities.iter_mut(){
+                *community = level_communities[*community];
+            }
+            graph = graph.aggregate(&level_communities);
+        }
+    }
+
+    let community_modularity = calc_community_modularity(&communities, edges);
+    CCommunityPartition{ modularity: community_modularity.iter().sum(), communities, community_modularity }
+}
+
+// 各社区对模块度的贡献: 内部边权重 / m - (度之和 / 2m)^2，communities为各节点所属社区的编号(从0开始连续)
+pub(crate) fn calc_community_modularity(communities: &[usize], edges: &[(usize, usize, f64)]) -> Vec<f64>{
+    let total_weight: f64 = edges.iter().map(|edge| edge.2).sum();
+    let community_amount = communities.iter().max().map(|max| max + 1).unwrap_or(0);
+    let mut internal_weights = vec![0.0; community_amount];
+    let mut degree_sums = vec![0.0; community_amount];
+    for (u, v, weight) in edges.iter(){
+        if communities[*u] == communities[*v]{
+            internal_weights[communities[*u]] += weight;
+        }
+        degree_sums[communities[*u]] += weight;
+        degree_sums[communities[*v]] += weight;
+    }
+    if total_weight > 0.0 {
+        internal_weights.iter().zip(degree_sums.iter())
+            .map(|(internal, degree_sum)| internal / total_weight - (degree_sum / (2.0 * total_weight)).powi(2))
+            .collect()
+    }else {
+        vec![0.0; community_amount]
+    }
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    /// 对已保存的相似度边列表(账号名a, 账号名b, 相似度)进行社区发现(Louvain)，返回与其他模式相同形式的分组结果。
+    /// 相似度低于edge_floor的边以及账号名不在集合中的边会被忽略，threshold中仅使用threshold_group_members
+    ///
+    /// Run community detection (Louvain) on a stored similarity edge list (account name a, account name b, similarity) and return the grouping result in the same form as the other modes.
+    /// Edges below edge_floor and edges whose names are not in the collection are ignored. Only threshold_group_members of threshold is used
+    pub fn group_by_communities_of_edge_list(&self, edges: &[(&str, &str, f64)], threshold: &CSimilarityGroupingThreshold, edge_floor: f64) -> CGroupingResult{
+        let index_table: HashMap<&str, usize> = (0..self.data_vec_size).map(|index| (self.account_name(index), index)).collect();
+        let index_edges: Vec<(usize, usize, f64)> = edges.iter()
+            .filter(|(name_a, name_b, similarity)| *similarity >= edge_floor && name_a != name_b)
+            .filter_map(|(name_a, name_b, similarity)| Some((*index_table.get(name_a)?, *index_table.get(name_b)?, *similarity)))
+            .collect();
+        let (index_map, annotations) = self.group_account_indexes_by_communities(index_edges);
+        let options = CGroupingOptions{ strategy: ClusteringStrategy::Community{ edge_floor }, ..Default::default() };
+        self.generate_grouping_result(index_map, &annotations, threshold.clone(), options)
+    }
+
+    // 由带权边进行社区发现，组长为社区内加权度最大的账号(相同时取序号最小者)
+    // 返回 (组长序号 -> 组员序号(组长在首位) 的映射, 含模块度的附加信息)；带权边保存在附加信息中，供组变化后重新计算模块度
+    pub(crate) fn group_account_indexes_by_communities(&self, edges: Vec<(usize, usize, f64)>) -> (HashMap<usize, Vec<usize>>, CClusteringAnnotations){
+        let partition = detect_communities(self.data_vec_size, &edges);
+        let mut weighted_degrees = vec![0.0; self.data_vec_size];
+        for (u, v, weight) in edges.iter(){
+            if partition.communities[*u] == partition.communities[*v]{
+                weighted_degrees[*u] += weight;
+                weighted_degrees[*v] += weight;
+            }
+        }
+        let mut community_members: Vec<Vec<usize>> = vec![Vec::new(); partition.community_modularity.len()];
+        for (index, community) in partition.communities.iter().enumerate(){
+            community_members[*community].push(index);
+        }
+
+        let mut index_map: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut annotations = CClusteringAnnotations{ modularity: Some(partition.modularity), similar_pairs: edges, ..Default::default() };
+        for (mut members, modularity) in community_members.into_iter().zip(partition.community_modularity){
+            let leader = *members.iter()
+                .max_by(|a, b| weighted_degrees[**a].partial_cmp(&weighted_degrees[**b]).unwrap_or(std::cmp::Ordering::Equal).then_with(|| b.cmp(a)))
+                .unwrap();
+            members.retain(|index| *index != leader);
+            members.insert(0, leader);
+            index_map.insert(leader, members);
+            annotations.community_modularity.insert(leader, modularity);
+        }
+        (index_map, annotations)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn it_works() {
+        // 两个三角形由一条边相连: 两个社区，模块度为 2 * (3/7 - (7/14)^2)
+        let edges = vec![(0, 1, 1.0), (1, 2, 1.0), (0, 2, 1.0), (3, 4, 1.0), (4, 5, 1.0), (3, 5, 1.0), (2, 3, 1.0)];
+        let partition = detect_communities(6, &edges);
+        assert_eq!(partition.communities, vec![0, 0, 0, 1, 1, 1]);
+        assert!((partition.modularity - (6.0 / 7.0 - 0.5)).abs() < 1e-12);
+        // 没有边时每个节点各成一个社区
+        let partition = detect_communities(3, &[]);
+        assert_eq!((partition.communities, partition.modularity), (vec![0, 1, 2], 0.0));
+
+        let account_list = std::fs::read_to_string("test_data/test_account_list.txt").unwrap();
+        let account_vec: Vec<&str> = account_list.lines().take(300).collect();
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.85, threshold_group_members: 1 };
+        let options = CGroupingOptions::default().set_strategy(ClusteringStrategy::Community{ edge_floor: 0.7 }).clone();
+        let result = ana.group_by_similarity_with_options(&threshold, &options);
+        let modularity = result.modularity.unwrap();
+        assert!(modularity > 0.3);
+        assert!((result.groups.iter().map(|g| g.modularity.unwrap()).sum::<f64>() - modularity).abs() < 1e-9);
+        assert_eq!(result.groups.iter().map(|g| g.size()).sum::<usize>(), ana.to_vec().len());
+
+        // 已保存的边列表与直接计算的结果一致
+        let matrix = ana.calc_sparse_similarity_matrix(0.7);
+        let stored_edges: Vec<(&str, &str, f64)> = matrix.edges.iter()
+            .map(|(a, b, similarity)| (matrix.names[*a].as_str(), matrix.names[*b].as_str(), *similarity))
+            .collect();
+        assert_eq!(ana.group_by_communities_of_edge_list(&stored_edges, &threshold, 0.7), result);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use rayon::prelude::*;
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+use crate::blocking::BlockingKey;
+use crate::grouping_result::{CClusteringAnnotations, DIAMETER_EXACT_GROUP_SIZE_LIMIT};
+
+
+// 并查集，用于合并相似账号对所在的集合
+pub(crate) struct CUnionFind{
+    parent: Vec<usize>,
+    rank: Vec<u8>
+}
+impl CUnionFind{
+    pub(crate) fn new(size: usize) -> CUnionFind{
+        CUnionFind{ parent: (0..size).collect(), rank: vec![0; size] }
+    }
+
+    // 查找所在集合的根，同时进行路径减半
+    pub(crate) fn find(&mut self, mut index: usize) -> usize{
+        while self.parent[index] != index{
+            self.parent[index] = self.parent[self.parent[index]];
+            index = self.parent[index];
+        }
+        index
+    }
+
+    // 合并两个元素所在的集合(按秩合并)
+    pub(crate) fn union(&mut self, a: usize, b: usize){
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a == root_b{
+            return;
+        }
+        match self.rank[root_a].cmp(&self.rank[root_b]) {
+            std::cmp::Ordering::Less => self.parent[root_a] = root_b,
+            std::cmp::Ordering::Greater => self.parent[root_b] = root_a,
+            std::cmp::Ordering::Equal => {
+                self.parent[root_b] = root_a;
+                self.rank[root_a] += 1;
+            }
+        }
+    }
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    // 连通分量(单链接)聚类: 找出相似度高于等于阈值的全部账号对，利用并查集合并为连通分量
+    // 组长为分量内相似账号最多的账号(数量相同时取序号最小者)
+    // 返回 (组长序号 -> 组员序号(组长在首位) 的映射, 含各分量直径的附加信息)
+    // 分块键不为空时只比较处于同一块的账号对
+    pub(crate) fn group_account_indexes_by_connected_components(&self, threshold_sim: f64, blocking_keys: &[BlockingKey]) -> (HashMap<usize, Vec<usize>>, CClusteringAnnotations){
+        self.group_account_indexes_by_similar_pairs(self.find_similar_index_pairs_by_blocking_keys(threshold_sim, blocking_keys))
+    }
+
+    // 将相似账号对(a, b, 相似度)合并为连通分量，组长及附加信息同上；相似账号对保存在附加信息中，供组变化后重新计算直径
+    pub(crate) fn group_account_indexes_by_similar_pairs(&self, edges: Vec<(usize, usize, f64)>) -> (HashMap<usize, Vec<usize>>, CClusteringAnnotations){
+        let adjacency = build_adjacency(self.data_vec_size, &edges);
+        let mut union_find = CUnionFind::new(self.data_vec_size);
+        for (a, b, _) in edges.iter(){
+            union_find.union(*a, *b);
+        }
+
+        let mut components: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for index in 0..self.data_vec_size{
+            components.entry(union_find.find(index)).or_default().push(index);
+        }
+        let groups: Vec<(usize, Vec<usize>, usize)> = components.into_values()
+            .collect::<Vec<Vec<usize>>>()
+            .into_par_iter()
+            .map(|mut members| {
+                let leader = *members.iter().min_by_key(|index| (std::cmp::Reverse(adjacency[**index].len()), **index)).unwrap();
+                let diameter = calc_component_diameter(&adjacency, &members);
+                members.retain(|index| *index != leader);
+                members.insert(0, leader);
+                (leader, members, diameter)
+            })
+            .collect();
+
+        let mut index_map: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut annotations = CClusteringAnnotations{ similar_pairs: edges, ..Default::default() };
+        for (leader, members, diameter) in groups{
+            index_map.insert(leader, members);
+            annotations.diameters.insert(leader, diameter);
+        }
+        (index_map, annotations)
+    }
+}
+
+// 构建相似度高于等于阈值的账号对组成的邻接表
+pub(crate) fn build_adjacency(size: usize, edges: &[(usize, usize, f64)]) -> Vec<Vec<usize>>{
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); size];
+    for (a, b, _) in edges.iter(){
+        adjacency[*a].push(*b);
+        adjacency[*b].push(*a);
+    }
+    adjacency
+}
+
+// 连通分量的直径(任意两账号间最短路径的最大跳数)
+// 分量大小不超过DIAMETER_EXACT_GROUP_SIZE_LIMIT时从每个账号出发做广度优先搜索得到精确值，否则使用两次广度优先搜索的估计值(不大于真实直径)
+fn calc_component_diameter(adjacency: &[Vec<usize>], members: &[usize]) -> usize{
+    if members.len() <= DIAMETER_EXACT_GROUP_SIZE_LIMIT {
+        members.iter().map(|start| farthest_by_bfs(adjacency, *start).1).max().unwrap_or(0)
+    }else {
+        let (farthest, _) = farthest_by_bfs(adjacency, members[0]);
+        farthest_by_bfs(adjacency, farthest).1
+    }
+}
+
+// 任意一组账号的直径，adjacency中只应包含组内账号之间的边；组在该图中不连通时返回None
+pub(crate) fn calc_group_diameter(adjacency: &[Vec<usize>], members: &[usize]) -> Option<usize>{
+    let reached = members.first().map(|start| reachable_amount_by_bfs(adjacency, *start)).unwrap_or(0);
+    if reached == members.len() { Some(calc_component_diameter(adjacency, members)) } else { None }
+}
+
+// 广度优先搜索，返回从起点可到达的账号数量(含起点)
+fn reachable_amount_by_bfs(adjacency: &[Vec<usize>], start: usize) -> usize{
+    let mut visited: HashSet<usize> = HashSet::new();
+    let mut queue = VecDeque::new();
+    visited.insert(start);
+    queue.push_back(start);
+    while let Some(index) = queue.pop_front(){
+        for neighbour in adjacency[index].iter(){
+            if visited.insert(*neighbour){
+                queue.push_back(*neighbour);
+            }
+        }
+    }
+    visited.len()
+}
+
+// 广度优先搜索，返回距离起点最远的账号(距离相同时取序号最小者)及其距离
+fn farthest_by_bfs(adjacency: &[Vec<usize>], start: usize) -> (usize, usize){
+    let mut distance_map: HashMap<usize, usize> = HashMap::new();
+    let mut queue = VecDeque::new();
+    let mut farthest = (start, 0);
+    distance_map.insert(start, 0);
+    queue.push_back(start);
+    while let Some(index) = queue.pop_front(){
+        let distance = distance_map[&index];
+        if distance > farthest.1 || (distance == farthest.1 && index < farthest.0){
+            farthest = (index, distance);
+        }
+        for neighbour in adjacency[index].iter(){
+            if !distance_map.contains_key(neighbour){
+                distance_map.insert(*neighbour, distance + 1);
+                queue.push_back(*neighbour);
+            }
+        }
+    }
+    farthest
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn it_works() {
+        let mut union_find = CUnionFind::new(5);
+        union_find.union(0, 1);
+        union_find.union(3, 4);
+        union_find.union(1, 4);
+        assert_eq!(union_find.find(0), union_find.find(3));
+        assert_ne!(union_find.find(0), union_find.find(2));
+
+        // 链状相似: 路径 0-1-2-3 的直径为3
+        let adjacency = vec![vec![1], vec![0, 2], vec![1, 3], vec![2]];
+        assert_eq!(calc_component_diameter(&adjacency, &[0, 1, 2, 3]), 3);
+        assert_eq!(calc_component_diameter(&[vec![]], &[0]), 0);
+        assert_eq!(calc_group_diameter(&adjacency, &[0, 1, 2, 3]), Some(3));
+        assert_eq!(calc_group_diameter(&[vec![], vec![]], &[0, 1]), None);
+        assert_eq!(calc_group_diameter(&[vec![]], &[0]), Some(0));
+        // 超过DIAMETER_EXACT_GROUP_SIZE_LIMIT时使用两次广度优先搜索的估计值，链状分量上与精确值一致
+        let size = DIAMETER_EXACT_GROUP_SIZE_LIMIT + 1;
+        let edges: Vec<(usize, usize, f64)> = (1..size).map(|i| (i - 1, i, 1.0)).collect();
+        let adjacency = build_adjacency(size, &edges);
+        let members: Vec<usize> = (size / 2..size).chain(0..size / 2).collect();
+        assert_eq!(calc_component_diameter(&adjacency, &members), size - 1);
+    }
+}
@@ -0,0 +1,117 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+use crate::grouping_result::CClusteringAnnotations;
+use crate::connected_components::CUnionFind;
+use crate::blocking::BlockingKey;
+
+
+/// 密度聚类中账号的角色
+///
+/// The role of an account in density clustering
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DensityRole {
+    Core,       // 核心账号: 邻域内账号数量(含自身)不少于min_points
+    Border,     // 边界账号: 自身不是核心账号，但与某个核心账号相似
+    Noise       // 噪声账号: 不与任何核心账号相似
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    // 密度聚类(DBSCAN): 邻域为相似度高于等于threshold_sim的其他账号
+    // 核心账号之间相似则属于同一簇；边界账号归入与其最相似的核心账号所在的簇(相似度相同时取序号最小者)；其余账号为噪声
+    // 组长为簇内邻域最大的核心账号(数量相同时取序号最小者)
+    // 返回 (组长序号 -> 组员序号(组长在首位) 的映射, 含各账号角色及噪声账号的附加信息)
+    // 分块键不为空时只比较处于同一块的账号对
+    pub(crate) fn group_account_indexes_by_density(&self, threshold_sim: f64, min_points: usize, blocking_keys: &[BlockingKey]) -> (HashMap<usize, Vec<usize>>, CClusteringAnnotations){
+        let size = self.data_vec_size;
+        let mut neighbours: Vec<Vec<(usize, f64)>> = vec![Vec::new(); size];
+        for (a, b, similarity) in self.find_similar_index_pairs_by_blocking_keys(threshold_sim, blocking_keys){
+            neighbours[a].push((b, similarity));
+            neighbours[b].push((a, similarity));
+        }
+        let is_core: Vec<bool> = neighbours.iter().map(|list| list.len() + 1 >= min_points).collect();
+
+        // 相似的核心账号合并为簇
+        let mut union_find = CUnionFind::new(size);
+        for (index, list) in neighbours.iter().enumerate(){
+            if is_core[index]{
+                for (neighbour, _) in list.iter().filter(|(neighbour, _)| is_core[*neighbour]){
+                    union_find.union(index, *neighbour);
+                }
+            }
+        }
+
+        let mut annotations = CClusteringAnnotations::default();
+        let mut clusters: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for index in 0..size{
+            if is_core[index]{
+                annotations.density_roles.insert(index, DensityRole::Core);
+                clusters.entry(union_find.find(index)).or_default().push(index);
+                continue;
+            }
+            let nearest_core = neighbours[index].iter()
+                .filter(|(neighbour, _)| is_core[*neighbour])
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal).then_with(|| b.0.cmp(&a.0)));
+            match nearest_core {
+                Some((core, _)) => {
+                    annotations.density_roles.insert(index, DensityRole::Border);
+                    clusters.entry(union_find.find(*core)).or_default().push(index);
+                },
+                None => {
+                    annotations.density_roles.insert(index, DensityRole::Noise);
+                    annotations.noise.push(index);
+                }
+            }
+        }
+
+        let mut index_map: HashMap<usize, Vec<usize>> = HashMap::new();
+        for mut members in clusters.into_values(){
+            let leader = *members.iter()
+                .filter(|index| is_core[**index])
+                .min_by_key(|index| (std::cmp::Reverse(neighbours[**index].len()), **index))
+                .unwrap();
+            members.retain(|index| *index != leader);
+            members.insert(0, leader);
+            index_map.insert(leader, members);
+        }
+        (index_map, annotations)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_account_name_by_similarity::{CSimilarityGroupingThreshold, CGroupingOptions, ClusteringStrategy};
+    #[test]
+    fn it_works() {
+        let account_list = std::fs::read_to_string("test_data/test_account_list.txt").unwrap();
+        let account_vec: Vec<&str> = account_list.lines().take(300).collect();
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.85, threshold_group_members: 1 };
+        let (index_map, annotations) = ana.group_account_indexes_by_density(threshold.threshold_sim, 20, &[]);
+        assert_eq!(annotations.density_roles.len(), ana.to_vec().len());
+        assert_eq!(index_map.values().map(|members| members.len()).sum::<usize>() + annotations.noise.len(), ana.to_vec().len());
+        for (leader, members) in index_map.iter(){
+            assert_eq!(members[0], *leader);
+            assert_eq!(annotations.density_roles[leader], DensityRole::Core);
+            assert!(members.iter().all(|index| annotations.density_roles[index] != DensityRole::Noise));
+        }
+
+        let options = CGroupingOptions::default().set_strategy(ClusteringStrategy::Density{ min_points: 20 }).clone();
+        let result = ana.group_by_similarity_with_options(&threshold, &options);
+        assert_eq!(result.noise_accounts.len(), annotations.noise.len());
+        assert!(!result.noise_accounts.is_empty());
+        assert!(result.groups.iter().all(|g| g.members[0].density_role == Some(DensityRole::Core)));
+        assert!(result.groups.iter().flat_map(|g| g.members.iter()).any(|m| m.density_role == Some(DensityRole::Border)));
+
+        // min_points为1时全部账号都是核心账号，结果与连通分量一致
+        let (index_map, annotations) = ana.group_account_indexes_by_density(threshold.threshold_sim, 1, &[]);
+        assert!(annotations.noise.is_empty());
+        assert_eq!(index_map, ana.group_account_indexes_by_connected_components(threshold.threshold_sim, &[]).0);
+    }
+}
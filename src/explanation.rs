@@ -0,0 +1,264 @@
+use std::fmt;
+use serde_json::json;
+use crate::algorithm::calc_common_prefix_length;
+use crate::analyze_account_name_similarity::{CAccountNameSimAnalyse, CAccountNameSimAnalyseParamsWeightTable, DEFAULT_PARAMETER_WEIGHT_TABLE};
+use crate::score_combiner::{ScoreCombiner, CScoreCombineContext, CEditDistanceJaroCombiner};
+
+
+// 单个分项的解释
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CComponentExplanation{
+    pub component: String,      // 分项名称，与CAccountNameSimResultDetail的字段名一致
+    pub raw_value: f64,         // 分项原始值
+    pub weight: f64,            // 分项在最终分数中的实际权重(贡献 = 原始值 * 权重)
+    pub share: f64,             // 分项的贡献，与组合器报告的贡献处于同一空间(见CSimilarityExplanation)
+    pub reason: String          // 通俗的说明
+}
+
+/// # Description
+/// * 对两个账号名相似度的解释: 最终分数以及各分项的原始值、实际权重、贡献和通俗说明。
+/// * The explanation of the similarity between two account names: the final score, and the raw value, effective weight, share and plain-language reason of each component.
+/// # Share
+/// * 贡献与组合器报告的贡献处于同一空间: 默认组合方式、加权平均与最大值为线性组合，全部贡献之和等于最终分数；
+///   几何平均的贡献之和等于最终分数，但按各分项在对数空间中的占比分摊；逻辑回归的贡献处于logit空间，全部贡献之和等于最终分数的logit值ln(s / (1 - s))。
+/// * Shares live in the same space as the contributions reported by the combiner: the default combiner, weighted mean and max are linear, so the shares add up to the final score.
+///   The geometric mean shares also add up to the final score, but they are split by each component's share in log space. The logistic shares are in logit space and add up to the logit of the final score, ln(s / (1 - s)).
+/// # Render
+/// * 可通过Display输出为文本表格，也可通过to_json输出为结构化JSON。
+/// * Can be rendered as a text table through Display, or as structured JSON through to_json.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CSimilarityExplanation{
+    pub account_name_a: String,
+    pub account_name_b: String,
+    pub similarity: f64,
+    pub components: Vec<CComponentExplanation>
+}
+impl CSimilarityExplanation{
+
+    /// 输出为结构化JSON，字段与结构体的字段同名
+    ///
+    /// Render as structured JSON, the keys are the field names of the struct
+    pub fn to_json(&self) -> String{
+        let components: Vec<_> = self.components.iter()
+            .map(|c| json!({
+                "component": c.component,
+                "raw_value": c.raw_value,
+                "weight": c.weight,
+                "share": c.share,
+                "reason": c.reason
+            }))
+            .collect();
+        json!({
+            "account_name_a": self.account_name_a,
+            "account_name_b": self.account_name_b,
+            "similarity": self.similarity,
+            "components": components
+        }).to_string()
+    }
+}
+
+impl fmt::Display for CSimilarityExplanation{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ~ {}: similarity {:.4}", self.account_name_a, self.account_name_b, self.similarity)?;
+        writeln!(f, "  {:<28} {:>8} {:>8} {:>8}  reason", "component", "raw", "weight", "share")?;
+        for c in self.components.iter(){
+            writeln!(f, "  {:<28} {:>8.4} {:>8.4} {:>8.4}  {}", c.component, c.raw_value, c.weight, c.share, c.reason)?;
+        }
+        Ok(())
+    }
+}
+
+
+impl<'a> CAccountNameSimAnalyse<'a>{
+
+    /// 解释两个账号名的相似度(默认权重表与默认组合方式)
+    ///
+    /// Explain the similarity between two account names (default weight table and default combiner)
+    pub fn explain(&self, obj_to_cmp: &CAccountNameSimAnalyse) -> CSimilarityExplanation{
+        self.explain_by_specify_combiner(obj_to_cmp, &DEFAULT_PARAMETER_WEIGHT_TABLE, &CEditDistanceJaroCombiner)
+    }
+
+    /// 按指定的权重表与组合方式解释两个账号名的相似度
+    ///
+    /// 组合方式报告的sim_score贡献会按权重表拆分到五个结构分项上，全部分项的贡献之和等于组合方式报告的贡献之和。
+    /// 线性组合方式下即为最终分数，逻辑回归下为最终分数的logit值(见CSimilarityExplanation)
+    ///
+    /// Explain the similarity between two account names with the given weight table and combiner.
+    /// The sim_score contribution reported by the combiner is split over the five structural components by the weight table, so the shares add up to the sum of the contributions reported by the combiner.
+    /// That is the final score for linear combiners and the logit of the final score for the logistic combiner (see CSimilarityExplanation)
+    pub fn explain_by_specify_combiner(&self,
+                                       obj_to_cmp: &CAccountNameSimAnalyse,
+                                       weight_table: &CAccountNameSimAnalyseParamsWeightTable,
+                                       combiner: &dyn ScoreCombiner) -> CSimilarityExplanation{
+        let (similarity, detail) = self.calc_similarity_by_specify_combiner(obj_to_cmp, weight_table, combiner);
+        let max_len = self.account_name.chars().count().max(obj_to_cmp.account_name.chars().count());
+
+        // 固定顺序的分项: 五个结构分项、jaro-winkler相似度、编辑距离
+        let mut components = vec![
+            explain_component("sim_skeleton_style", detail.sim_skeleton_style, self.reason_skeleton_style(obj_to_cmp)),
+            explain_component("sim_skeleton_part_size_list", detail.sim_skeleton_part_size_list,
+                                   compare_reason("part sizes", &join_sizes(&self.skeleton_part_size_list), &join_sizes(&obj_to_cmp.skeleton_part_size_list))),
+            explain_component("sim_length", detail.sim_length, compare_reason("length", &self.length.to_string(), &obj_to_cmp.length.to_string())),
+            explain_component("sim_item_list", detail.sim_item_list, self.reason_item_list(obj_to_cmp)),
+            explain_component("sim_item_amount", detail.sim_item_amount, compare_reason("part amount", &self.item_amount.to_string(), &obj_to_cmp.item_amount.to_string())),
+            explain_component("sim_jaro_distance", detail.sim_jaro_distance,
+                                   format!("jaro-winkler on characters, common prefix {}", calc_common_prefix_length(self.account_name, obj_to_cmp.account_name))),
+            explain_component("sim_edit_distance", detail.sim_edit_distance as f64,
+                                   format!("edit distance {} over length {}", detail.sim_edit_distance, max_len)),
+        ];
+
+        let weight_sum = weight_table.weight_sum();
+        let structural_weights = [("sim_skeleton_style", weight_table.skeleton_skeleton_style),
+                                  ("sim_skeleton_part_size_list", weight_table.skeleton_part_size_list),
+                                  ("sim_length", weight_table.length),
+                                  ("sim_item_list", weight_table.item_list),
+                                  ("sim_item_amount", weight_table.item_amount)];
+        let context = CScoreCombineContext::new(self, obj_to_cmp);
+        for contribution in combiner.contributions(&detail, &context){
+            // sim_score为结构分项的加权平均，按权重表拆分
+            if contribution.component == "sim_score" && weight_sum > 0.0 {
+                let sim_score_weight = if contribution.value != 0.0 { contribution.contribution / contribution.value } else { 0.0 };
+                for (name, weight) in structural_weights.iter(){
+                    let c = components.iter_mut().find(|c| c.component == *name).unwrap();
+                    c.weight += sim_score_weight * weight / weight_sum;
+                    c.share += sim_score_weight * weight / weight_sum * c.raw_value;
+                }
+                continue;
+            }
+            match components.iter_mut().find(|c| c.component == contribution.component) {
+                Some(c) => {
+                    if contribution.value != 0.0 {
+                        c.weight += contribution.contribution / contribution.value;
+                    }
+                    c.share += contribution.contribution;
+                },
+                None => components.push(CComponentExplanation{
+                    component: contribution.component.to_string(),
+                    raw_value: contribution.value,
+                    weight: if contribution.value != 0.0 { contribution.contribution / contribution.value } else { 0.0 },
+                    share: contribution.contribution,
+                    reason: match contribution.component {
+                        "sim_edit_similarity" => format!("1 - edit distance {} / length {}", detail.sim_edit_distance, max_len),
+                        "intercept" => String::from("intercept of the score combiner"),
+                        _ => String::from("reported by the score combiner")
+                    }
+                })
+            }
+        }
+
+        CSimilarityExplanation{
+            account_name_a: self.account_name.to_string(),
+            account_name_b: obj_to_cmp.account_name.to_string(),
+            similarity,
+            components
+        }
+    }
+
+    // 骨架说明，例如 "same template s-i with 4-digit counter"
+    fn reason_skeleton_style(&self, obj_to_cmp: &CAccountNameSimAnalyse) -> String{
+        let (template_a, template_b) = (join_template(&self.skeleton_style), join_template(&obj_to_cmp.skeleton_style));
+        if template_a != template_b {
+            return format!("different templates {} vs {}", template_a, template_b);
+        }
+        let digit_sizes: Vec<String> = self.skeleton_style.iter().zip(self.skeleton_part_size_list.iter())
+            .zip(obj_to_cmp.skeleton_part_size_list.iter())
+            .filter(|((style, _), _)| **style == b'i')
+            .map(|((_, size_a), size_b)| if size_a == size_b { size_a.to_string() } else { format!("{}/{}", size_a, size_b) })
+            .collect();
+        match digit_sizes.len() {
+            0 => format!("same template {}", template_a),
+            1 => format!("same template {} with {}-digit counter", template_a, digit_sizes[0]),
+            _ => format!("same template {} with {}-digit counters", template_a, digit_sizes.join(", "))
+        }
+    }
+
+    // 零件集合说明，例如 "1 of 2 aligned parts identical (ubut)"
+    fn reason_item_list(&self, obj_to_cmp: &CAccountNameSimAnalyse) -> String{
+        let (items_a, items_b) = (self.item_list(), obj_to_cmp.item_list());
+        let aligned = items_a.len().min(items_b.len());
+        let same_items: Vec<String> = items_a.iter().zip(items_b.iter())
+            .filter(|(a, b)| a == b)
+            .map(|(a, _)| a.iter().filter_map(|c| std::char::from_u32(*c as u32)).collect())
+            .collect();
+        if same_items.is_empty() {
+            format!("0 of {} aligned parts identical", aligned)
+        }else {
+            format!("{} of {} aligned parts identical ({})", same_items.len(), aligned, same_items.join(", "))
+        }
+    }
+}
+
+
+fn explain_component(component: &str, raw_value: f64, reason: String) -> CComponentExplanation{
+    CComponentExplanation{ component: component.to_string(), raw_value, weight: 0.0, share: 0.0, reason }
+}
+
+fn join_template(skeleton_style: &[u8]) -> String{
+    skeleton_style.iter().map(|c| (*c as char).to_string()).collect::<Vec<String>>().join("-")
+}
+
+fn join_sizes(part_size_list: &[u32]) -> String{
+    part_size_list.iter().map(|c| c.to_string()).collect::<Vec<String>>().join("-")
+}
+
+fn compare_reason(name: &str, value_a: &str, value_b: &str) -> String{
+    if value_a == value_b {
+        format!("same {} {}", name, value_a)
+    }else {
+        format!("{} {} vs {}", name, value_a, value_b)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score_combiner::{CWeightedMeanCombiner, CGeometricMeanCombiner, CLogisticCombiner};
+    #[test]
+    fn it_works() {
+        let (obj_a, obj_b) = (CAccountNameSimAnalyse::new("ubut2739"), CAccountNameSimAnalyse::new("ubut1179"));
+        let explanation = obj_a.explain(&obj_b);
+        assert_eq!(explanation.similarity, obj_a.calc_similarity(&obj_b).0);
+        assert!((explanation.components.iter().map(|c| c.share).sum::<f64>() - explanation.similarity).abs() < 1e-12);
+        assert!(explanation.components.iter().all(|c| (c.raw_value * c.weight - c.share).abs() < 1e-12));
+        assert_eq!(explanation.components[0].reason, "same template s-i with 4-digit counter");
+        assert_eq!(explanation.components[3].reason, "1 of 2 aligned parts identical (ubut)");
+        assert_eq!(explanation.components[6].reason, "edit distance 3 over length 8");
+
+        let explanation = CAccountNameSimAnalyse::new("zz2871369").explain(&obj_a);
+        assert_eq!(explanation.components[0].reason, "same template s-i with 7/4-digit counter");
+        let explanation = CAccountNameSimAnalyse::new("zz2871369a").explain(&obj_a);
+        assert_eq!(explanation.components[0].reason, "different templates s-i-s vs s-i");
+
+        // 自定义组合方式
+        let combiner = CWeightedMeanCombiner::default();
+        let explanation = obj_a.explain_by_specify_combiner(&obj_b, &DEFAULT_PARAMETER_WEIGHT_TABLE, &combiner);
+        assert!((explanation.components.iter().map(|c| c.share).sum::<f64>() - explanation.similarity).abs() < 1e-12);
+        assert_eq!(explanation.components[7].component, "sim_edit_similarity");
+        assert_eq!(explanation.components[7].reason, "1 - edit distance 3 / length 8");
+
+        // 几何平均的贡献之和等于最终分数，逻辑回归的贡献之和等于最终分数的logit值
+        let explanation = obj_a.explain_by_specify_combiner(&obj_b, &DEFAULT_PARAMETER_WEIGHT_TABLE, &CGeometricMeanCombiner::default());
+        assert!((explanation.components.iter().map(|c| c.share).sum::<f64>() - explanation.similarity).abs() < 1e-12);
+        let logistic = CLogisticCombiner::new(-3.0, [2.0, 2.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let logistic_explanation = obj_a.explain_by_specify_combiner(&obj_b, &DEFAULT_PARAMETER_WEIGHT_TABLE, &logistic);
+        let logit = (logistic_explanation.similarity / (1.0 - logistic_explanation.similarity)).ln();
+        assert!((logistic_explanation.components.iter().map(|c| c.share).sum::<f64>() - logit).abs() < 1e-9);
+        assert_eq!(logistic_explanation.components.iter().find(|c| c.component == "intercept").unwrap().share, -3.0);
+        println!("{}", obj_a.explain(&obj_b));
+
+        let json = explanation.to_json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["account_name_a"], json!("ubut2739"));
+        assert_eq!(value["similarity"], json!(explanation.similarity));
+        assert_eq!(value["components"].as_array().unwrap().len(), explanation.components.len());
+        assert_eq!(value["components"][6]["reason"], json!(explanation.components[6].reason));
+        #[cfg(feature = "serde")]
+        assert_eq!(serde_json::from_str::<CSimilarityExplanation>(&json).unwrap(), explanation);
+        println!("{}", json);
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use rayon::prelude::*;
+use crate::analyze_account_name_similarity::CAccountNameSimAnalyse;
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+use crate::similarity_matrix::escape_csv_field;
+
+
+// 骨架哈希桶的数量
+pub const SKELETON_HASH_BUCKET_AMOUNT: usize = 16;
+
+// 特征向量的长度
+pub const FEATURE_VECTOR_LEN: usize = 12 + SKELETON_HASH_BUCKET_AMOUNT;
+
+/// 特征向量各列的名称，与feature_vector返回值的顺序一致。列名与顺序保持稳定，新增特征只会追加在末尾
+///
+/// Column names of the feature vector, in the same order as the values returned by feature_vector. Names and order are stable, new features are only appended at the end
+pub const FEATURE_COLUMN_NAMES: [&str; FEATURE_VECTOR_LEN] = [
+    "length",                   // 字符数量
+    "segment_count",            // 零件数量
+    "letter_count",             // 字母数量
+    "digit_count",              // 数字数量
+    "other_count",              // 其他字符数量
+    "letter_ratio",             // 字母占比
+    "digit_ratio",              // 数字占比
+    "uppercase_ratio",          // 大写字母占字母的比例
+    "longest_digit_run",        // 最长连续数字长度
+    "longest_letter_run",       // 最长连续字母长度
+    "digit_segment_count",      // 数字零件数量
+    "char_entropy",             // 字符香农熵(比特)
+    "skeleton_hash_bucket_0",   // 骨架(零件类型及长度)哈希后的独热编码
+    "skeleton_hash_bucket_1",
+    "skeleton_hash_bucket_2",
+    "skeleton_hash_bucket_3",
+    "skeleton_hash_bucket_4",
+    "skeleton_hash_bucket_5",
+    "skeleton_hash_bucket_6",
+    "skeleton_hash_bucket_7",
+    "skeleton_hash_bucket_8",
+    "skeleton_hash_bucket_9",
+    "skeleton_hash_bucket_10",
+    "skeleton_hash_bucket_11",
+    "skeleton_hash_bucket_12",
+    "skeleton_hash_bucket_13",
+    "skeleton_hash_bucket_14",
+    "skeleton_hash_bucket_15",
+];
+
+
+/// # Description
+/// * 账号集合的特征表，每行为一个账号名的定长特征向量，列名见FEATURE_COLUMN_NAMES。
+/// * The feature table of an account collection. Each row is the fixed-length feature vector of one account name, see FEATURE_COLUMN_NAMES for the columns.
+/// # Export
+/// * 支持导出为CSV；启用arrow特性后支持导出为Arrow RecordBatch及Arrow IPC文件。
+/// * Can be exported as CSV. With the arrow feature it can also be exported as an Arrow RecordBatch or an Arrow IPC file.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CFeatureTable{
+    pub names: Vec<String>,
+    pub rows: Vec<Vec<f64>>
+}
+impl CFeatureTable{
+
+    /// 导出CSV，首列为account_name，其余列按FEATURE_COLUMN_NAMES排列
+    ///
+    /// Export as CSV. The first column is account_name, followed by the columns of FEATURE_COLUMN_NAMES
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()>{
+        writeln!(writer, "account_name,{}", FEATURE_COLUMN_NAMES.join(","))?;
+        for (name, row) in self.names.iter().zip(self.rows.iter()){
+            write!(writer, "{}", escape_csv_field(name))?;
+            for value in row.iter(){
+                write!(writer, ",{}", value)?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl CFeatureTable{
+
+    /// 转换为Arrow RecordBatch，首列为account_name(Utf8)，其余列为Float64
+    ///
+    /// Convert to an Arrow RecordBatch. The first column is account_name (Utf8) and the others are Float64
+    pub fn to_record_batch(&self) -> Result<arrow_array::RecordBatch, arrow_schema::ArrowError>{
+        use std::sync::Arc;
+        use arrow_array::{ArrayRef, Float64Array, StringArray};
+        use arrow_schema::{DataType, Field, Schema};
+
+        let mut fields = vec![Field::new("account_name", DataType::Utf8, false)];
+        fields.extend(FEATURE_COLUMN_NAMES.iter().map(|name| Field::new(*name, DataType::Float64, false)));
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(StringArray::from_iter_values(self.names.iter()))];
+        for column in 0..FEATURE_VECTOR_LEN{
+            columns.push(Arc::new(Float64Array::from_iter_values(self.rows.iter().map(|row| row[column]))));
+        }
+        arrow_array::RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+    }
+
+    /// 导出Arrow IPC文件(Feather V2)
+    ///
+    /// Export an Arrow IPC file (Feather V2)
+    pub fn write_arrow_ipc<W: Write>(&self, writer: W) -> Result<(), arrow_schema::ArrowError>{
+        let batch = self.to_record_batch()?;
+        let mut ipc_writer = arrow_ipc::writer::FileWriter::try_new(writer, &batch.schema())?;
+        ipc_writer.write(&batch)?;
+        ipc_writer.finish()
+    }
+}
+
+
+impl<'a> CAccountNameSimAnalyse<'a>{
+
+    /// 计算定长特征向量，各值的含义见FEATURE_COLUMN_NAMES
+    ///
+    /// Calculate the fixed-length feature vector, see FEATURE_COLUMN_NAMES for the meaning of each value
+    pub fn feature_vector(&self) -> [f64; FEATURE_VECTOR_LEN]{
+        let mut result = [0.0; FEATURE_VECTOR_LEN];
+        let mut char_count_table: HashMap<char, usize> = HashMap::new();
+        let (mut letter_count, mut digit_count, mut uppercase_count, mut length) = (0, 0, 0, 0);
+        let (mut letter_run, mut longest_letter_run) = (0, 0);
+        for c in self.account_name.chars(){
+            length += 1;
+            *(char_count_table.entry(c).or_insert(0)) += 1;
+            if c.is_alphabetic(){
+                letter_count += 1;
+                letter_run += 1;
+                longest_letter_run = longest_letter_run.max(letter_run);
+                if c.is_uppercase(){
+                    uppercase_count += 1;
+                }
+            }else {
+                letter_run = 0;
+                if c.is_ascii_digit(){
+                    digit_count += 1;
+                }
+            }
+        }
+
+        let digit_part_sizes = self.skeleton_style.iter().zip(self.skeleton_part_size_list.iter())
+            .filter(|(style, _)| **style == b'i')
+            .map(|(_, size)| *size as usize);
+        let ratio = |count: usize, total: usize| if total == 0 { 0.0 } else { count as f64 / total as f64 };
+
+        // 按字符排序后累加，保证结果与HashMap的遍历顺序无关
+        let mut char_counts: Vec<(char, usize)> = char_count_table.into_iter().collect();
+        char_counts.sort_unstable();
+        let entropy = char_counts.iter()
+            .map(|(_, count)| ratio(*count, length))
+            .map(|p| -p * p.log2())
+            .sum::<f64>();
+
+        result[0] = length as f64;
+        result[1] = self.item_amount as f64;
+        result[2] = letter_count as f64;
+        result[3] = digit_count as f64;
+        result[4] = (length - letter_count - digit_count) as f64;
+        result[5] = ratio(letter_count, length);
+        result[6] = ratio(digit_count, length);
+        result[7] = ratio(uppercase_count, letter_count);
+        result[8] = digit_part_sizes.clone().max().unwrap_or(0) as f64;
+        result[9] = longest_letter_run as f64;
+        result[10] = digit_part_sizes.count() as f64;
+        result[11] = entropy;
+        result[12 + self.skeleton_hash_bucket()] = 1.0;
+        result
+    }
+
+    // 骨架(零件类型及长度)的哈希桶序号，使用FNV-1a以保证不同平台、不同版本之间结果一致
+    fn skeleton_hash_bucket(&self) -> usize{
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let bytes = self.skeleton_style.iter().copied()
+            .chain(self.skeleton_part_size_list.iter().flat_map(|size| size.to_le_bytes()));
+        for byte in bytes{
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        (hash % SKELETON_HASH_BUCKET_AMOUNT as u64) as usize
+    }
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    /// 多线程计算全部账号名的特征向量，行顺序与to_vec()一致
+    ///
+    /// Calculate the feature vectors of all account names in parallel, the row order is the same as to_vec()
+    pub fn calc_feature_table(&self) -> CFeatureTable{
+        CFeatureTable{
+            names: self.to_vec(),
+            rows: (0..self.data_vec_size).into_par_iter().map(|index| self.analyse_obj(index)).map(|obj| obj.feature_vector().to_vec()).collect()
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn it_works() {
+        let features = CAccountNameSimAnalyse::new("Ubut2739x12").feature_vector();
+        assert_eq!(&features[..11], &[11.0, 4.0, 5.0, 6.0, 0.0, 5.0 / 11.0, 6.0 / 11.0, 0.2, 4.0, 4.0, 2.0]);
+        assert_eq!(features[12..].iter().sum::<f64>(), 1.0);
+        assert_eq!(CAccountNameSimAnalyse::new("aaaa").feature_vector()[11], 0.0);
+        assert_eq!(CAccountNameSimAnalyse::new("abcd").feature_vector()[11], 2.0);
+        assert_eq!(CAccountNameSimAnalyse::new("").feature_vector()[..12], [0.0; 12]);
+        // 骨架相同的账号落入同一个哈希桶
+        assert_eq!(CAccountNameSimAnalyse::new("ubut2739").feature_vector()[12..], CAccountNameSimAnalyse::new("zzzz0001").feature_vector()[12..]);
+
+        let account_vec = vec!["ubut2739", "ubut1179", "zz2871369", "用户2739", "a,b"];
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let table = ana.calc_feature_table();
+        assert_eq!(table.names, ana.to_vec());
+        assert!(table.rows.iter().all(|row| row.len() == FEATURE_VECTOR_LEN));
+
+        let mut csv = Vec::new();
+        table.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 1 + account_vec.len());
+        assert!(csv.lines().all(|line| line.matches(',').count() >= FEATURE_VECTOR_LEN));
+
+        #[cfg(feature = "arrow")]
+        {
+            let mut ipc = Vec::new();
+            table.write_arrow_ipc(&mut ipc).unwrap();
+            let reader = arrow_ipc::reader::FileReader::try_new(std::io::Cursor::new(ipc), None).unwrap();
+            let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(batches[0], table.to_record_batch().unwrap());
+            assert_eq!(batches[0].num_columns(), 1 + FEATURE_VECTOR_LEN);
+            assert_eq!(batches[0].num_rows(), account_vec.len());
+        }
+        println!("{}", csv);
+    }
+}
@@ -1,9 +1,12 @@
 use std::cmp::min;
-use std::collections::{HashSet, HashMap};
+use std::collections::HashMap;
 use itertools::Itertools;
 use crossbeam::channel as channel;
 use lazy_static::lazy_static;
 use crate::analyze_account_name_similarity::{CAccountNameSimAnalyse};
+use crate::leader_reelection::LeaderRecheckAction;
+use crate::blocking::BlockingKey;
+use crate::min_hash_lsh::CMinHashLshParams;
 
 
 // CPU数量
@@ -14,19 +17,30 @@ lazy_static! {
     static ref DEFAULT_MASSIVE_DATA_THRETHOLD: usize = (*DEFAULT_THREAD_MAX).pow(2) * 600;
 }
 
+// 确定性模式下使用的固定分割粒度及大数据量阈值，与CPU数量无关(相当于8核机器上的默认值)
+const DETERMINISTIC_GROUP_GRANULARITY: usize = 9 * 9 * 400;
+const DETERMINISTIC_MASSIVE_DATA_THRETHOLD: usize = 9 * 9 * 600;
+
+// 分组工作函数的签名
+type GroupWorkerFn<'a, P> = fn(&CAccountNameAnaVec<'a, P>, &[usize], &CSimilarityGroupingThreshold, &CGroupingOptions) -> HashMap<usize, Vec<usize>>;
+
 
 // 用于对账号集合进行分组的判定阈值
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CSimilarityGroupingThreshold{
     pub threshold_sim: f64,                 // 相似度阈值,高于等于这个阈值则判定两个账号相似,可以被分为一组
     pub threshold_group_members: usize      // 组员数量阈值,过滤掉成员数量较少的组.  例如threshold_group_members=3, 则返回的结果中仅包含组员数大于3的账号组
 }
-impl CSimilarityGroupingThreshold{
-    pub fn default() -> CSimilarityGroupingThreshold{
+impl Default for CSimilarityGroupingThreshold{
+    fn default() -> CSimilarityGroupingThreshold{
         CSimilarityGroupingThreshold{
             threshold_sim: 0.856,
             threshold_group_members: 2
         }
     }
+}
+impl CSimilarityGroupingThreshold{
     pub fn set_threshold_sim(&mut self, threshold_sim: f64) -> &mut CSimilarityGroupingThreshold {
         self.threshold_sim = threshold_sim;
         self
@@ -38,16 +52,116 @@ impl CSimilarityGroupingThreshold{
 }
 
 // 效率档位
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EfficiencyMode {
     Accurately = 0,
     Normal = 1,
+    #[default]
     Quickly = 2,
     Rapidly = 3
 }
-impl EfficiencyMode{
-    pub fn default() -> EfficiencyMode{
-        EfficiencyMode::Quickly
+
+// 聚类策略
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ClusteringStrategy {
+    #[default]
+    Leader,                 // 组长模式: 账号仅在与组长相似时加入该组
+    ConnectedComponents,    // 连通分量(单链接): 相似度高于等于阈值的账号对视为相连，每个连通分量为一组
+    Density{                // 密度聚类(DBSCAN): 邻域为相似度高于等于阈值的账号，邻域内账号数量(含自身)不少于min_points的为核心账号
+        min_points: usize
+    },
+    Community{              // 社区发现(Louvain): 以相似度高于等于edge_floor的账号对为带权边，按模块度划分社区，不使用threshold_sim
+        edge_floor: f64
+    },
+    MinHashLsh(CMinHashLshParams)   // MinHash LSH: 仅对共享某个桶的候选账号对计算相似度，相似度高于等于阈值的账号对按连通分量合并为组
+}
+
+// 分组选项
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CGroupingOptions{
+    pub mode: EfficiencyMode,           // 效率档位，仅用于组长模式
+    pub strategy: ClusteringStrategy,   // 聚类策略
+    pub b_deterministic: bool,          // 确定性模式: 使用与CPU数量无关的固定分割粒度，相同的输入与参数总会得到完全相同的结果
+    pub b_weighted_group_size: bool,    // 按出现次数之和(而非不同账号名的数量)判断组员数量是否达到threshold_group_members
+    pub refinement_rounds: usize,       // 分组后k-medoids式精修(合并组长相似的组、重选中心点、组员移入最匹配的组)的最大轮数，为0时不精修
+    pub b_reelect_medoid_leader: bool,  // 分组后将各组组长重选为中心点(与其他组员相似度之和最大的组员)
+    pub leader_recheck_action: LeaderRecheckAction,     // 重选组长后，对与新组长相似度低于threshold_sim的组员的处理方式
+    pub blocking_keys: Vec<BlockingKey>,                // 分块键。组长模式: 第一个键决定数据块的划分，其余各键依次再进行一轮组长相似的组间合并，为空时按骨架分块；
+                                                        // 连通分量/密度聚类/社区发现: 只比较至少在一个键下处于同一块的账号对，为空时比较全部账号对；
+                                                        // 精修及重选组长后移入其他组: 组长及组员只与至少在一个键下处于同一块的组长比较，为空时按骨架分块
+    pub thread_amount: usize,           // 组长模式的最大线程数量，为0时使用CPU数量+1；只影响运行效率，不影响分组结果
+    pub group_granularity: usize        // 组长模式的分割粒度，大数据量阈值为其1.5倍；为0时使用默认值(确定性模式下为固定值，否则由CPU数量决定)
+}
+impl CGroupingOptions{
+    pub fn set_mode(&mut self, mode: EfficiencyMode) -> &mut CGroupingOptions {
+        self.mode = mode;
+        self
+    }
+    pub fn set_strategy(&mut self, strategy: ClusteringStrategy) -> &mut CGroupingOptions {
+        self.strategy = strategy;
+        self
+    }
+    pub fn set_deterministic(&mut self, b_deterministic: bool) -> &mut CGroupingOptions {
+        self.b_deterministic = b_deterministic;
+        self
+    }
+    pub fn set_weighted_group_size(&mut self, b_weighted_group_size: bool) -> &mut CGroupingOptions {
+        self.b_weighted_group_size = b_weighted_group_size;
+        self
+    }
+    pub fn set_refinement_rounds(&mut self, refinement_rounds: usize) -> &mut CGroupingOptions {
+        self.refinement_rounds = refinement_rounds;
+        self
+    }
+    pub fn set_reelect_medoid_leader(&mut self, b_reelect_medoid_leader: bool) -> &mut CGroupingOptions {
+        self.b_reelect_medoid_leader = b_reelect_medoid_leader;
+        self
+    }
+    pub fn set_leader_recheck_action(&mut self, leader_recheck_action: LeaderRecheckAction) -> &mut CGroupingOptions {
+        self.leader_recheck_action = leader_recheck_action;
+        self
+    }
+    pub fn set_blocking_keys(&mut self, blocking_keys: Vec<BlockingKey>) -> &mut CGroupingOptions {
+        self.blocking_keys = blocking_keys;
+        self
+    }
+    pub fn set_thread_amount(&mut self, thread_amount: usize) -> &mut CGroupingOptions {
+        self.thread_amount = thread_amount;
+        self
+    }
+    pub fn set_group_granularity(&mut self, group_granularity: usize) -> &mut CGroupingOptions {
+        self.group_granularity = group_granularity;
+        self
+    }
+
+    // 记录到分组结果中的选项: 去掉只影响运行效率的线程数量；确定性模式下与固定分割粒度相同的分割粒度记为0，
+    // 保证分组结果(及其序列化结果)与线程数量无关
+    pub(crate) fn recorded(&self) -> CGroupingOptions{
+        let mut recorded = self.clone();
+        recorded.thread_amount = 0;
+        if recorded.b_deterministic && recorded.group_granularity == DETERMINISTIC_GROUP_GRANULARITY{
+            recorded.group_granularity = 0;
+        }
+        recorded
+    }
+
+    // 实际使用的最大线程数量
+    fn thread_max(&self) -> usize{
+        if self.thread_amount > 0 { self.thread_amount } else { *DEFAULT_THREAD_MAX }
+    }
+
+    // 实际使用的分割粒度及大数据量阈值
+    fn group_granularity_and_massive_data_threshold(&self) -> (usize, usize){
+        if self.group_granularity > 0 {
+            (self.group_granularity, self.group_granularity * 3 / 2)
+        }else if self.b_deterministic {
+            (DETERMINISTIC_GROUP_GRANULARITY, DETERMINISTIC_MASSIVE_DATA_THRETHOLD)
+        }else {
+            (*DEFAULT_GROUP_GRANULARITY, *DEFAULT_MASSIVE_DATA_THRETHOLD)
+        }
     }
 }
 
@@ -68,29 +182,166 @@ impl EfficiencyMode{
 ///
 /// * 从命名上就能很轻易地看出，group_by_similarity_rapidly的运行效率最高，group_by_similarity_accurately的精准度最高。
 /// * 传入的threshold_sim、threshold_group_members两项参数会很大程度影响运行的效率
-pub struct CAccountNameAnaVec<'a>{
-    analyse_obj_vec: Vec<CAccountNameSimAnalyse<'a>>,
-    data_vec_size: usize,
+/// * 通过new_with_records初始化时，P为记录所附带载荷的类型，其余情况下为()
+/// * P is the type of the payloads attached to the records when built with new_with_records, and () otherwise
+pub struct CAccountNameAnaVec<'a, P = ()>{
+    analyse_spans: Vec<CAnalyseSpan<'a>>,           // 各账号名及其分析数据在共享存储中的位置
+    skeleton_styles: Vec<u8>,                       // 全部账号名的零件类型，按账号顺序连续存储
+    skeleton_part_sizes: Vec<u32>,                  // 全部账号名的零件字符数量，与skeleton_styles一一对应
+    wide_char_codes: Vec<u32>,                      // 非ASCII账号名的全部字符编码，连续存储
+    occurrence_counts: Option<Vec<u64>>,            // 各账号名在输入中的出现次数，与analyse_spans一一对应；全部为1时不保存
+    payloads: Option<Vec<Vec<&'a P>>>,              // 各账号名对应的记录所附带的载荷，与analyse_spans一一对应；仅通过new_with_records初始化时保存
+    pub(crate) data_vec_size: usize,
+}
+
+// 账号名及其分析数据在共享存储中的位置: 零件类型与零件字符数量从part_start开始共part_amount个，
+// 非ASCII账号名的字符编码从wide_char_start开始，数量为各零件字符数量之和
+#[derive(Clone, Copy, Debug)]
+struct CAnalyseSpan<'a>{
+    account_name: &'a str,
+    part_start: u32,
+    part_amount: u32,
+    wide_char_start: Option<u32>
 }
 impl<'a> CAccountNameAnaVec<'a>{
 
-    /// 这是这个类的初始化函数。传入账号名集合，初始化流程中会使用特定规则会对其进行初步的去重、排序
+    /// 这是这个类的初始化函数。传入账号名集合，初始化流程中会使用特定规则会对其进行初步的去重、排序，并记录每个账号名的出现次数
     ///
     /// This is the initialization function of this class.
-    /// You need to pass in a set of account names as parameters, and specific rules will be used in the initialization process to perform preliminary deduplication and sorting
+    /// You need to pass in a set of account names as parameters, and specific rules will be used in the initialization process to perform preliminary deduplication and sorting.
+    /// The occurrence count of every account name is kept
     pub fn new(account_name_vec: &'a Vec<&str>) -> CAccountNameAnaVec<'a> {
-        let mut obj_vec: Vec<CAccountNameSimAnalyse<'a>> = Vec::new();
+        CAccountNameAnaVec::new_by_iter(account_name_vec.iter().map(|name| (*name, 1, None)))
+    }
 
-        // 去重
-        let account_name_hash_set: HashSet<&&str> = account_name_vec.into_iter().collect();
-        for &item in account_name_hash_set{
-            obj_vec.push(CAccountNameSimAnalyse::new(item));
+    /// 传入(账号名, 出现次数)集合进行初始化，例如来自登录日志的统计结果。重复的账号名会合并并累加出现次数
+    ///
+    /// Initialize with (account name, occurrence count) pairs, e.g. counts taken from login logs. Repeated account names are merged and their counts are added up
+    pub fn new_with_counts(account_name_counts: &'a [(&'a str, u64)]) -> CAccountNameAnaVec<'a> {
+        CAccountNameAnaVec::new_by_iter(account_name_counts.iter().map(|(name, count)| (*name, *count, None)))
+    }
+
+}
+impl<'a, P> CAccountNameAnaVec<'a, P>{
+
+    /// 传入(账号名, 载荷)记录进行初始化，载荷可以是用户ID、注册时间等任意类型的数据，不做转换也不复制。
+    /// 同名的记录会合并为一个账号名，并按记录顺序保留这些记录的载荷，出现次数即记录数量。
+    /// 通过payloads或members_with_payloads可直接取回原始载荷
+    ///
+    /// Initialize with (account name, payload) records. The payload can be any data, such as a user ID or a registration time, and is neither converted nor copied.
+    /// Records with the same name are merged into one account name that keeps the payloads of those records, in record order, and the occurrence count is the number of records.
+    /// Use payloads or members_with_payloads to get the original payloads back
+    pub fn new_with_records(records: &'a [(&'a str, P)]) -> CAccountNameAnaVec<'a, P> {
+        CAccountNameAnaVec::new_by_iter(records.iter().map(|(name, payload)| (*name, 1, Some(payload))))
+    }
+
+    fn new_by_iter<I: Iterator<Item = (&'a str, u64, Option<&'a P>)>>(records: I) -> CAccountNameAnaVec<'a, P> {
+        // 去重并统计出现次数、收集载荷
+        let mut record_map: HashMap<&'a str, (u64, Vec<&'a P>)> = HashMap::new();
+        let mut b_with_payloads = false;
+        for (name, count, payload) in records{
+            let entry = record_map.entry(name).or_insert((0, Vec::new()));
+            entry.0 += count;
+            b_with_payloads |= payload.is_some();
+            entry.1.extend(payload);
         }
+        let mut obj_vec: Vec<(CAccountNameSimAnalyse<'a>, (u64, Vec<&'a P>))> = record_map.into_iter()
+            .map(|(name, record)| (CAccountNameSimAnalyse::new(name), record))
+            .collect();
 
         // 排序
-        obj_vec.sort_by_key(|k|
-            (k.skeleton_style.to_vec(), k.skeleton_part_size_list.to_vec(), k.account_name.to_string()));
-        CAccountNameAnaVec{ data_vec_size: obj_vec.len(), analyse_obj_vec: obj_vec}
+        obj_vec.sort_by(|(a, _), (b, _)|
+            (&a.skeleton_style, &a.skeleton_part_size_list, a.account_name).cmp(&(&b.skeleton_style, &b.skeleton_part_size_list, b.account_name)));
+        let b_with_counts = obj_vec.iter().any(|(_, (count, _))| *count != 1);
+        let part_total: usize = obj_vec.iter().map(|(obj, _)| obj.skeleton_style.len()).sum();
+        let wide_char_total: usize = obj_vec.iter().filter_map(|(obj, _)| obj.wide_char_codes()).map(|codes| codes.len()).sum();
+        let mut ana_vec = CAccountNameAnaVec{
+            data_vec_size: obj_vec.len(),
+            analyse_spans: Vec::with_capacity(obj_vec.len()),
+            skeleton_styles: Vec::with_capacity(part_total),
+            skeleton_part_sizes: Vec::with_capacity(part_total),
+            wide_char_codes: Vec::with_capacity(wide_char_total),
+            occurrence_counts: if b_with_counts { Some(Vec::with_capacity(obj_vec.len())) } else { None },
+            payloads: if b_with_payloads { Some(Vec::with_capacity(obj_vec.len())) } else { None }
+        };
+        // 各分析对象的数据移入共享存储，偏移量以u32保存
+        assert!(part_total <= u32::MAX as usize && wide_char_total <= u32::MAX as usize, "the analysis data of all account names must fit in u32 offsets");
+        for (obj, (count, payloads)) in obj_vec{
+            ana_vec.analyse_spans.push(CAnalyseSpan{
+                account_name: obj.account_name,
+                part_start: ana_vec.skeleton_styles.len() as u32,
+                part_amount: obj.item_amount,
+                wide_char_start: obj.wide_char_codes().map(|_| ana_vec.wide_char_codes.len() as u32)
+            });
+            ana_vec.skeleton_styles.extend_from_slice(&obj.skeleton_style);
+            ana_vec.skeleton_part_sizes.extend_from_slice(&obj.skeleton_part_size_list);
+            ana_vec.wide_char_codes.extend_from_slice(obj.wide_char_codes().unwrap_or_default());
+            if let Some(occurrence_counts) = ana_vec.occurrence_counts.as_mut() {
+                occurrence_counts.push(count);
+            }
+            if let Some(payload_table) = ana_vec.payloads.as_mut() {
+                payload_table.push(payloads);
+            }
+        }
+        ana_vec
+    }
+
+    // 第index个账号名
+    pub(crate) fn account_name(&self, index: usize) -> &'a str{
+        self.analyse_spans[index].account_name
+    }
+
+    // 第index个账号名的分析对象，借用共享存储中的数据，不分配内存
+    pub(crate) fn analyse_obj(&self, index: usize) -> CAccountNameSimAnalyse<'_>{
+        let span = &self.analyse_spans[index];
+        let parts = span.part_start as usize..(span.part_start + span.part_amount) as usize;
+        let skeleton_part_size_list = &self.skeleton_part_sizes[parts.clone()];
+        let wide_char_codes = span.wide_char_start.map(|start| {
+            let char_amount: u32 = skeleton_part_size_list.iter().sum();
+            &self.wide_char_codes[start as usize..(start + char_amount) as usize]
+        });
+        CAccountNameSimAnalyse::from_borrowed_parts(span.account_name, &self.skeleton_styles[parts], skeleton_part_size_list, wide_char_codes)
+    }
+
+    /// 账号名分析数据占用的内存大小(字节)，包括各账号名的位置信息及共享存储，不包括借用的账号名、出现次数及载荷
+    ///
+    /// The memory used by the analysis data in bytes, including the position of every account name and the shared storage,
+    /// but neither the borrowed account names nor the occurrence counts and payloads
+    pub fn memory_footprint(&self) -> usize{
+        self.analyse_spans.len() * std::mem::size_of::<CAnalyseSpan>() +
+            self.skeleton_styles.len() * std::mem::size_of::<u8>() +
+            self.skeleton_part_sizes.len() * std::mem::size_of::<u32>() +
+            self.wide_char_codes.len() * std::mem::size_of::<u32>()
+    }
+
+    /// 第index个账号名(顺序与to_vec()一致)的出现次数
+    ///
+    /// The occurrence count of the account name at index, in the same order as to_vec()
+    pub fn occurrence_count(&self, index: usize) -> u64{
+        match &self.occurrence_counts {
+            Some(occurrence_counts) => occurrence_counts[index],
+            None => 1
+        }
+    }
+
+    /// 第index个账号名(顺序与to_vec()一致)对应的全部记录的载荷，按记录顺序排列。未通过new_with_records初始化时为空
+    ///
+    /// The payloads of all records with the account name at index (in the same order as to_vec()), in record order. Empty unless built with new_with_records
+    pub fn payloads(&self, index: usize) -> &[&'a P]{
+        match &self.payloads {
+            Some(payloads) => &payloads[index],
+            None => &[]
+        }
+    }
+
+    /// 全部账号名的出现次数之和
+    ///
+    /// The sum of the occurrence counts of all account names
+    pub fn total_occurrences(&self) -> u64{
+        match &self.occurrence_counts {
+            Some(occurrence_counts) => occurrence_counts.iter().sum(),
+            None => self.data_vec_size as u64
+        }
     }
 
     /// 返回排序后的账号名集合
@@ -99,11 +350,20 @@ impl<'a> CAccountNameAnaVec<'a>{
     pub fn to_vec(&self) -> Vec<String>{
         let mut res: Vec<String> = Vec::new();
         for i in 0..self.data_vec_size{
-            res.push(self.analyse_obj_vec[i].account_name.parse().unwrap());
+            res.push(self.account_name(i).parse().unwrap());
         }
         res
     }
 
+    /// 返回排序后的账号名分析对象集合，各分析对象借用共享存储中的数据
+    ///
+    /// Return the sorted account name analysis objects, which borrow their data from the shared storage
+    pub fn analyses(&self) -> Vec<CAccountNameSimAnalyse<'_>>{
+        (0..self.data_vec_size).map(|index| self.analyse_obj(index)).collect()
+    }
+}
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
     /// # 功能
     /// 以账号之间的相似度作为判断标准对账号集合进行分组，高度相似的账号会被分到一组。
     ///
@@ -140,68 +400,84 @@ impl<'a> CAccountNameAnaVec<'a>{
     // 根据分割粒度将数据分割成若干数据块，并分别交由子线程处理,最后进行数据汇总
     // 设置合理的分割粒度可以在保证准确性的基础上提高运算效率
     fn group_account_names_by_similarity(&self, threshold: &mut CSimilarityGroupingThreshold, mode: &EfficiencyMode) -> HashMap<usize, Vec<String>>{
-        let group_index_map: HashMap<usize, Vec<usize>>;
+        let group_index_map = self.group_account_indexes_by_similarity(threshold, &CGroupingOptions{ mode: *mode, ..CGroupingOptions::default() });
+        self.generate_group_map_by_index(&group_index_map, threshold.threshold_group_members)
+    }
+
+    // 基于相似度对账号序号进行分组，返回 组长序号 -> 组员序号(组长在首位) 的映射，尚未按组员数量阈值过滤
+    pub(crate) fn group_account_indexes_by_similarity(&self, threshold: &mut CSimilarityGroupingThreshold, options: &CGroupingOptions) -> HashMap<usize, Vec<usize>>{
+        let mut group_index_map: HashMap<usize, Vec<usize>>;
+        let (group_granularity, massive_data_threshold) = options.group_granularity_and_massive_data_threshold();
         if threshold.threshold_sim > 1.0{
             threshold.threshold_sim = 1.0;
         }
         // 数据量较大，需要采用 group_massive_accounts
-        if self.data_vec_size >= *DEFAULT_MASSIVE_DATA_THRETHOLD{
-            group_index_map = self.group_massive_accounts(&(0..self.data_vec_size).collect_vec(), threshold, *DEFAULT_GROUP_GRANULARITY, mode);
+        if self.data_vec_size >= massive_data_threshold{
+            group_index_map = self.group_massive_accounts(&(0..self.data_vec_size).collect_vec(), threshold, group_granularity, options);
         }else {
             // 处理小数据量的账号
-            match mode {
-                EfficiencyMode::Accurately | EfficiencyMode::Normal  => {group_index_map = self.group_accurately(&(0..self.data_vec_size).collect_vec(), threshold);},
-                EfficiencyMode::Quickly    | EfficiencyMode::Rapidly => {group_index_map = self.group_quickly(&(0..self.data_vec_size).collect_vec(), threshold);},
+            match options.mode {
+                EfficiencyMode::Accurately | EfficiencyMode::Normal  => {group_index_map = self.group_accurately(&(0..self.data_vec_size).collect_vec(), threshold, options);},
+                EfficiencyMode::Quickly    | EfficiencyMode::Rapidly => {group_index_map = self.group_quickly(&(0..self.data_vec_size).collect_vec(), threshold, options);},
             };
         }
-
-        self.generate_group_map_by_index(&group_index_map, threshold.threshold_group_members)
+        // 其余分块键: 使分块键相同或排序相邻的近似账号总能被比较
+        // 第一个键为排序邻域时，数据块按骨架划分，窗口同样通过组间合并处理
+        let merge_keys_start = match options.blocking_keys.first() {
+            Some(BlockingKey::SortedNeighborhood{..}) => 0,
+            _ => 1
+        };
+        for blocking_key in options.blocking_keys.iter().skip(merge_keys_start){
+            group_index_map = self.merge_groups_by_blocking_key(group_index_map, *blocking_key, threshold.threshold_sim);
+        }
+        group_index_map
     }
 
     // 对大量数据进行分组
-    fn group_massive_accounts(&self, index_vec: &Vec<usize>, threshold: &CSimilarityGroupingThreshold, group_granularity: usize, mode: &EfficiencyMode) -> HashMap<usize, Vec<usize>>{
+    fn group_massive_accounts(&self, index_vec: &[usize], threshold: &CSimilarityGroupingThreshold, group_granularity: usize, options: &CGroupingOptions) -> HashMap<usize, Vec<usize>>{
         let mut b_efficient = false;
-        let mut fn_pointer: fn(&CAccountNameAnaVec<'a>, &Vec<usize>, &CSimilarityGroupingThreshold) -> HashMap<usize, Vec<usize>> = CAccountNameAnaVec::group_accurately;
-        match mode {
+        let mut fn_pointer: GroupWorkerFn<'a, P> = CAccountNameAnaVec::group_accurately;
+        match options.mode {
             EfficiencyMode::Accurately => {},
             EfficiencyMode::Normal     => {b_efficient = true;},
             EfficiencyMode::Quickly    => {fn_pointer = CAccountNameAnaVec::group_quickly;},
             EfficiencyMode::Rapidly    => {b_efficient = true; fn_pointer = CAccountNameAnaVec::group_quickly;}
         };
-        let account_groups_vec = self.split_index_vec(index_vec, group_granularity);
-        self.fn_handler_group(&account_groups_vec, threshold,  b_efficient, &fn_pointer)
+        let account_groups_vec = self.split_index_vec(index_vec, group_granularity, options);
+        self.fn_handler_group(&account_groups_vec, threshold,  b_efficient, options, &fn_pointer)
     }
 
     // 对少量数据准确分组
-    fn group_accurately(&self, index_list: &Vec<usize>, threshold: &CSimilarityGroupingThreshold) -> HashMap<usize, Vec<usize>>{
-        self.basic_worker_group(index_list, threshold, 400, false)
+    fn group_accurately(&self, index_list: &[usize], threshold: &CSimilarityGroupingThreshold, options: &CGroupingOptions) -> HashMap<usize, Vec<usize>>{
+        self.basic_worker_group(index_list, threshold, 400, false, options)
     }
 
     // 对少量数据快速分组
-    fn group_quickly(&self, index_list: &Vec<usize>, threshold: &CSimilarityGroupingThreshold) -> HashMap<usize, Vec<usize>>{
-        self.basic_worker_group(index_list, threshold, 400, true)
+    fn group_quickly(&self, index_list: &[usize], threshold: &CSimilarityGroupingThreshold, options: &CGroupingOptions) -> HashMap<usize, Vec<usize>>{
+        self.basic_worker_group(index_list, threshold, 400, true, options)
     }
 
     // 对数据进行分组
-    fn basic_worker_group(&self, index_vec: &Vec<usize>, threshold: &CSimilarityGroupingThreshold, group_granularity: usize, b_efficient: bool) -> HashMap<usize, Vec<usize>>{
-        let fn_pointer: fn(&CAccountNameAnaVec<'a>, &Vec<usize>, &CSimilarityGroupingThreshold) -> HashMap<usize, Vec<usize>> = CAccountNameAnaVec::worker_group_accounts_bottommost;
-        let account_groups_vec = self.split_index_vec(index_vec, group_granularity);
+    fn basic_worker_group(&self, index_vec: &[usize], threshold: &CSimilarityGroupingThreshold, group_granularity: usize, b_efficient: bool, options: &CGroupingOptions) -> HashMap<usize, Vec<usize>>{
+        let fn_pointer: GroupWorkerFn<'a, P> = CAccountNameAnaVec::worker_group_accounts_bottommost;
+        let account_groups_vec = self.split_index_vec(index_vec, group_granularity, options);
         self.fn_handler_group(&account_groups_vec,
                               &CSimilarityGroupingThreshold {
                                   threshold_sim: threshold.threshold_sim,
                                   threshold_group_members: min(group_granularity / 100, threshold.threshold_group_members) },
-                              b_efficient, &fn_pointer)
+                              b_efficient, options, &fn_pointer)
     }
 
     // 传入函数指针,handler内部多线程执行该函数并将结果汇总
     // 分为快速模式和精准模式,如果需要快速计算,可将b_efficient设置为true,这可能会导致少量数据被遗弃,但在计算大量数据的过程中可以显著提高效率
     // b_recursion用于退出合并递归,主动调用fn_handler_group时该值均为true
-    fn fn_handler_group(&self, account_groups_vec: &Vec<Vec<usize>>, threshold: &CSimilarityGroupingThreshold, b_efficient: bool, fn_pointer: &fn(&CAccountNameAnaVec<'a>, &Vec<usize>, &CSimilarityGroupingThreshold) -> HashMap<usize, Vec<usize>>) -> HashMap<usize, Vec<usize>>{
+    fn fn_handler_group(&self, account_groups_vec: &[Vec<usize>], threshold: &CSimilarityGroupingThreshold, b_efficient: bool, options: &CGroupingOptions, fn_pointer: &GroupWorkerFn<'a, P>) -> HashMap<usize, Vec<usize>>{
         let thread_num = account_groups_vec.len();
+        let thread_max = options.thread_max();
 
         // 单线程可处理
         if thread_num == 1{
-            return fn_pointer(&self, &account_groups_vec[0], threshold);
+            return fn_pointer(self, &account_groups_vec[0], threshold, options);
         }
 
         // 需要用到多线程
@@ -209,11 +485,11 @@ impl<'a> CAccountNameAnaVec<'a>{
         let (s, r) = channel::bounded(thread_num);
         match thread_num{
             // 不需要使用线程池
-            thread_num if thread_num <= *DEFAULT_THREAD_MAX => {
-                for i in 0..thread_num{
+            thread_num if thread_num <= thread_max => {
+                for (chunk_index, account_group) in account_groups_vec.iter().enumerate(){
                     crossbeam::scope(|scope| {
                         scope.spawn(|_|{
-                            s.clone().send(fn_pointer(&self, &account_groups_vec[i].to_vec(), threshold)).unwrap();
+                            s.clone().send((chunk_index, fn_pointer(self, account_group, threshold, options))).unwrap();
                         });
                     }).unwrap();
                 };
@@ -221,30 +497,31 @@ impl<'a> CAccountNameAnaVec<'a>{
 
             // 使用线程池，避免线程切换/申请/销毁占用过多资源
             _ => {
-                let pool = rayon::ThreadPoolBuilder::new().num_threads(*DEFAULT_THREAD_MAX).build().unwrap();
-                for i in 0..thread_num{
-                    pool.install(|| s.clone().send(fn_pointer(&self, &account_groups_vec[i].to_vec(), threshold)).unwrap());
+                let pool = rayon::ThreadPoolBuilder::new().num_threads(thread_max).build().unwrap();
+                for (chunk_index, account_group) in account_groups_vec.iter().enumerate(){
+                    pool.install(|| s.clone().send((chunk_index, fn_pointer(self, account_group, threshold, options))).unwrap());
                 };
             }
         };
 
 
-        // 整合数据
-        for _i in 0..thread_num{
-            let mut map_to_integrate = r.recv().unwrap();
+        // 整合数据，按数据块的顺序合并，保证结果与线程的完成顺序无关
+        let mut chunk_results = (0..thread_num).map(|_| r.recv().unwrap()).collect_vec();
+        chunk_results.sort_by_key(|chunk_result| chunk_result.0);
+        for (_chunk_index, mut map_to_integrate) in chunk_results{
             // 优化掉一些低频数据, 效率高，但会造成部分数据的丢失
             if b_efficient{
-                self.filter_low_frequency_data(&mut map_to_integrate, threshold.threshold_group_members);
+                self.filter_low_frequency_data(&mut map_to_integrate, threshold.threshold_group_members, options.b_weighted_group_size);
             }
 
             // 数据合并
-            self.integrate_two_group_map(&mut result, &map_to_integrate, threshold.threshold_sim);
+            self.integrate_two_group_map(&mut result, &map_to_integrate, threshold.threshold_sim, thread_max);
         }
         result
     }
 
     // 最底层的工作者线程
-    fn worker_group_accounts_bottommost(&self, index_list: &Vec<usize>, threshold: &CSimilarityGroupingThreshold) -> HashMap<usize, Vec<usize>>{
+    fn worker_group_accounts_bottommost(&self, index_list: &[usize], threshold: &CSimilarityGroupingThreshold, _options: &CGroupingOptions) -> HashMap<usize, Vec<usize>>{
         let mut group_map: HashMap<usize, Vec<usize>> = HashMap::new();
         for index in index_list{
             self.integrate_account_into_groups(*index, &mut group_map, threshold.threshold_sim);
@@ -253,7 +530,7 @@ impl<'a> CAccountNameAnaVec<'a>{
     }
 
     // 将两个存储账号组信息的map融合
-    fn integrate_two_group_map(&self, dst: &mut HashMap<usize, Vec<usize>>, src: &HashMap<usize, Vec<usize>>, threshold_sim: f64){
+    fn integrate_two_group_map(&self, dst: &mut HashMap<usize, Vec<usize>>, src: &HashMap<usize, Vec<usize>>, threshold_sim: f64, thread_max: usize){
         let thread_num = src.len();
         if thread_num == 0{
             return;
@@ -261,9 +538,10 @@ impl<'a> CAccountNameAnaVec<'a>{
 
         let (s, r) = channel::bounded(thread_num);
         let origin_dst = dst.clone();
+        let src_keys = src.keys().copied().sorted().collect_vec();
         match thread_num {
-            thread_num if thread_num <= *DEFAULT_THREAD_MAX =>{
-                for key in src.keys(){
+            thread_num if thread_num <= thread_max =>{
+                for key in src_keys.iter(){
                     crossbeam::scope(|scope| {
                         scope.spawn(|_|{
                             s.clone().send((*key, self.determine_which_group_the_account_belongs_to(*key, &origin_dst, threshold_sim))).unwrap();
@@ -272,15 +550,17 @@ impl<'a> CAccountNameAnaVec<'a>{
                 };
             },
             _ => {
-                let pool = rayon::ThreadPoolBuilder::new().num_threads(*DEFAULT_THREAD_MAX).build().unwrap();
-                for key in src.keys(){
+                let pool = rayon::ThreadPoolBuilder::new().num_threads(thread_max).build().unwrap();
+                for key in src_keys.iter(){
                     pool.install(|| s.clone().send((*key, self.determine_which_group_the_account_belongs_to(*key, &origin_dst, threshold_sim))).unwrap());
                 };
             }
         }
 
-        for _i in 0..thread_num{
-            let integrate_data = r.recv().unwrap();
+        // 按组长序号的顺序合并，保证结果与HashMap的遍历顺序及线程的完成顺序无关
+        let mut integrate_data_vec = (0..thread_num).map(|_| r.recv().unwrap()).collect_vec();
+        integrate_data_vec.sort_by_key(|integrate_data| integrate_data.0);
+        for integrate_data in integrate_data_vec{
             if integrate_data.0 == integrate_data.1{
                 dst.entry(integrate_data.1).or_insert(src[&integrate_data.0].clone());
             }else {
@@ -303,9 +583,12 @@ impl<'a> CAccountNameAnaVec<'a>{
     // 判断某账号所属的组  返回组长的序号  若返回的序号为该账号自身的序号，则说明它不属于任何一组
     fn determine_which_group_the_account_belongs_to(&self, index_to_match: usize, group_map: &HashMap<usize, Vec<usize>>, threshold: f64) -> usize{
         let mut index_vec_to_iter = group_map.keys().collect_vec();
-        index_vec_to_iter.sort_by_cached_key(|k| ((**k as i64 - index_to_match as i64).abs()));
+        // 距离相同时按组长序号排序，保证匹配顺序固定
+        index_vec_to_iter.sort_by_cached_key(|k| ((**k as i64 - index_to_match as i64).abs(), **k));
+        let obj_to_match = self.analyse_obj(index_to_match);
         for group_leader_index in index_vec_to_iter{
-            if self.analyse_obj_vec[*group_leader_index].calc_similarity(&self.analyse_obj_vec[index_to_match]).0 >= threshold{
+            // 先通过低成本的相似度上界排除不可能达到阈值的组长
+            if self.analyse_obj(*group_leader_index).calc_similarity_at_least(&obj_to_match, threshold).is_some(){
                 return *group_leader_index;
             }
         }
@@ -313,36 +596,19 @@ impl<'a> CAccountNameAnaVec<'a>{
     }
 
     // 分割成不同源账号组,用于多线程运算
-    fn split_index_vec(&self, index_vec: &Vec<usize>, group_granularity: usize) -> Vec<Vec<usize>>{
+    // 先按第一个分块键(默认为骨架)划分数据块，再按分割粒度切分各数据块
+    fn split_index_vec(&self, index_vec: &[usize], group_granularity: usize, options: &CGroupingOptions) -> Vec<Vec<usize>>{
         let mut result: Vec<Vec<usize>>= Vec::new();
-        let total_size = index_vec.len();
-        if total_size == 0{
-            return result;
-        }
-
-        let mut index_vec_group_by_skeleton: Vec<Vec<usize>> = Vec::new();
-        index_vec_group_by_skeleton.push(vec![index_vec[0]]);
-        for index in 1..index_vec.len(){
-            if &self.analyse_obj_vec[index_vec[index - 1]].skeleton_style == &self.analyse_obj_vec[index_vec[index]].skeleton_style &&
-                &self.analyse_obj_vec[index_vec[index - 1]].skeleton_part_size_list == &self.analyse_obj_vec[index_vec[index]].skeleton_part_size_list
-            {
-                let current_tail_index = index_vec_group_by_skeleton.len() - 1;
-                index_vec_group_by_skeleton.get_mut(current_tail_index).unwrap().push(index_vec[index]);
-            }
-            else {
-                index_vec_group_by_skeleton.push(vec![index_vec[index]]);
-            }
-        }
-        index_vec_group_by_skeleton.sort_by(|a, b| b.len().cmp(&a.len()));
-        for index in 0..index_vec_group_by_skeleton.len(){
-            result.append(&mut self.split_index_vec_by_granularity(&index_vec_group_by_skeleton[index], group_granularity));
+        let blocking_key = options.blocking_keys.first().copied().unwrap_or_default();
+        for index_vec_in_block in &self.partition_index_vec_by_blocking_key(index_vec, blocking_key){
+            result.append(&mut self.split_index_vec_by_granularity(index_vec_in_block, group_granularity));
         }
 
         result
     }
 
     // 根据分组粒度对数据进行分组,返回各组成员的序号
-    fn split_index_vec_by_granularity(&self, index_vec: &Vec<usize>, group_granularity: usize) -> Vec<Vec<usize>>{
+    fn split_index_vec_by_granularity(&self, index_vec: &[usize], group_granularity: usize) -> Vec<Vec<usize>>{
         let mut result: Vec<Vec<usize>>= Vec::new();
         let data_vec_size = index_vec.len();
         let real_group_granularity = min(group_granularity, data_vec_size);
@@ -366,11 +632,10 @@ impl<'a> CAccountNameAnaVec<'a>{
 
     // 生成账号组信息表
     fn generate_group_map_by_index(&self, index_map: &HashMap<usize, Vec<usize>>, threshold_group_members: usize) -> HashMap<usize, Vec<String>>{
-        let mut group_index: usize = 0;
         let mut group_map: HashMap<usize, Vec<String>> = HashMap::new();
         let mut group_vec = index_map.iter().collect_vec();
-        group_vec.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
-        for group in group_vec{
+        group_vec.sort_by_key(|b| (std::cmp::Reverse(b.1.len()), *b.0));
+        for (group_index, group) in group_vec.into_iter().enumerate(){
             // 因为前面排过序，所以当遇到组员数量少于阈值的情况直接结束遍历
             if group.1.len() < threshold_group_members{
                 break;
@@ -378,20 +643,28 @@ impl<'a> CAccountNameAnaVec<'a>{
 
             let mut group_detail: Vec<String> = Vec::new();
             for index in group.1{
-                group_detail.push(self.analyse_obj_vec[*index].account_name.parse().unwrap());
+                group_detail.push(self.account_name(*index).parse().unwrap());
             }
             group_map.entry(group_index).or_insert(group_detail);
-            group_index += 1;
         }
         group_map
     }
 
+    // 账号组的大小: 不同账号名的数量，或按出现次数加权后的数量
+    pub(crate) fn group_size(&self, member_indexes: &[usize], b_weighted: bool) -> u64{
+        if b_weighted {
+            member_indexes.iter().map(|index| self.occurrence_count(*index)).sum()
+        }else {
+            member_indexes.len() as u64
+        }
+    }
+
     // 过滤部分低频数据
-    fn filter_low_frequency_data(&self, src: &mut HashMap<usize, Vec<usize>>, threshold: usize){
+    fn filter_low_frequency_data(&self, src: &mut HashMap<usize, Vec<usize>>, threshold: usize, b_weighted: bool){
         // 舍弃低频数据
         let mut remove_vec: Vec<usize> = Vec::new();
         for it in src.iter_mut(){
-            if it.1.len() < threshold{
+            if self.group_size(it.1, b_weighted) < threshold as u64{
                 remove_vec.push(*it.0);
             }
         }
@@ -409,7 +682,6 @@ impl<'a> CAccountNameAnaVec<'a>{
 mod tests {
     extern crate serde_json;
 
-    use std::fs;
     use super::*;
 
     #[test]
@@ -445,5 +717,30 @@ mod tests {
         // for item in _res.iter(){
         //     println!("{}-{:?}", item.0, item.1)
         // }
+
+        // 重复账号名合并后保留出现次数
+        let dup_vec = vec!["b2c", "a1f6", "b2c", "b2c"];
+        let dup_ana = CAccountNameAnaVec::new(&dup_vec);
+        assert_eq!(dup_ana.to_vec().len(), 2);
+        assert_eq!(dup_ana.occurrence_count(dup_ana.to_vec().iter().position(|name| name == "b2c").unwrap()), 3);
+        assert_eq!(dup_ana.total_occurrences(), 4);
+        assert!(dup_ana.occurrence_counts.is_some() && dup_ana.payloads.is_none());
+        // 无重复、无载荷时不保存出现次数及载荷
+        let unique_vec = vec!["b2c", "a1f6"];
+        let unique_ana = CAccountNameAnaVec::new(&unique_vec);
+        assert!(unique_ana.occurrence_counts.is_none() && unique_ana.payloads.is_none());
+        assert_eq!((unique_ana.occurrence_count(1), unique_ana.payloads(1).len(), unique_ana.total_occurrences()), (1, 0, 2));
+        // 载荷保持调用方的类型，不做复制
+        let records = [("b2c", vec![7u8]), ("a1f6", vec![8u8])];
+        let record_ana = CAccountNameAnaVec::new_with_records(&records);
+        assert!(record_ana.occurrence_counts.is_none());
+        let payloads = record_ana.payloads(record_ana.to_vec().iter().position(|name| name == "b2c").unwrap());
+        assert_eq!(payloads, [&vec![7u8]]);
+        assert!(std::ptr::eq(payloads[0], &records[0].1));
+
+        // 分组结果中记录的选项不含线程数量，确定性模式下与固定分割粒度相同的分割粒度记为0
+        let deterministic = CGroupingOptions::default().set_deterministic(true).clone();
+        assert_eq!(deterministic.clone().set_thread_amount(8).set_group_granularity(DETERMINISTIC_GROUP_GRANULARITY).recorded(), deterministic);
+        assert_eq!(CGroupingOptions::default().set_group_granularity(DETERMINISTIC_GROUP_GRANULARITY).recorded().group_granularity, DETERMINISTIC_GROUP_GRANULARITY);
     }
 }
\ No newline at end of file
@@ -0,0 +1,447 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use rayon::prelude::*;
+use crate::group_account_name_by_similarity::{CAccountNameAnaVec, CSimilarityGroupingThreshold, EfficiencyMode, CGroupingOptions, ClusteringStrategy};
+use crate::density_clustering::DensityRole;
+use crate::leader_reelection::CLeaderReelectionReport;
+use crate::refinement::CRefinementReport;
+use crate::min_hash_lsh::CMinHashLshReport;
+use crate::connected_components::{build_adjacency, calc_group_diameter};
+use crate::community_detection::calc_community_modularity;
+
+
+// 序列化格式的版本号，格式发生不兼容的变化时递增
+pub const GROUPING_RESULT_FORMAT_VERSION: u32 = 1;
+
+// 组员数量不超过该值时，组内相似度统计覆盖全部组员对；超过时仅统计各组员与组长之间的相似度，避免平方级的计算量
+pub const COHESION_EXACT_GROUP_SIZE_LIMIT: usize = 256;
+
+// 组员数量不超过该值时，diameter从每个组员出发做广度优先搜索得到精确值；超过时使用两次广度优先搜索的估计值(不大于真实直径)
+pub const DIAMETER_EXACT_GROUP_SIZE_LIMIT: usize = 512;
+
+
+// 组员、其在CAccountNameAnaVec中的序号(可直接用于payloads、occurrence_count)、其与组长的相似度以及在输入中的出现次数
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CGroupMember{
+    pub account_name: String,
+    pub account_index: usize,
+    pub similarity_to_leader: f64,
+    pub occurrence_count: u64,
+    pub density_role: Option<DensityRole>     // 密度聚类中的角色(核心/边界)，其他策略下为None
+}
+
+// 各聚类策略附带的额外信息，以账号序号为键
+#[derive(Default)]
+pub(crate) struct CClusteringAnnotations{
+    pub(crate) diameters: HashMap<usize, usize>,            // 组长序号 -> 组直径
+    pub(crate) density_roles: HashMap<usize, DensityRole>,  // 账号序号 -> 密度聚类中的角色
+    pub(crate) noise: Vec<usize>,                           // 密度聚类中的噪声账号
+    pub(crate) community_modularity: HashMap<usize, f64>,   // 组长序号 -> 社区对模块度的贡献
+    pub(crate) modularity: Option<f64>,                     // 社区划分的模块度
+    pub(crate) min_hash_lsh: Option<CMinHashLshReport>,     // MinHash LSH分组的统计
+    pub(crate) similar_pairs: Vec<(usize, usize, f64)>      // 连通分量/MinHash LSH/社区发现所用的相似账号对，用于组变化后重新计算直径及模块度
+}
+impl CClusteringAnnotations{
+    // 组员发生变化(精修、重选组长)后，按最终的分组重新计算以组长为键的直径及模块度，原有的值全部丢弃
+    // 直径按保存的相似账号对在组内构成的图计算，组在该图中不连通时为None；模块度按最终分组重新计算各组的贡献及总和
+    pub(crate) fn refresh_group_annotations(&mut self, index_map: &HashMap<usize, Vec<usize>>, account_amount: usize){
+        let b_diameters = !self.diameters.is_empty();
+        let b_modularity = self.modularity.is_some();
+        if !b_diameters && !b_modularity{
+            return;
+        }
+        // 各账号所属组的编号(按组长序号排列)，不属于任何组的账号各自编号
+        let mut leaders: Vec<usize> = index_map.keys().copied().collect();
+        leaders.sort_unstable();
+        let mut group_of: Vec<Option<usize>> = vec![None; account_amount];
+        for (group_index, leader) in leaders.iter().enumerate(){
+            for index in index_map[leader].iter(){
+                group_of[*index] = Some(group_index);
+            }
+        }
+
+        if b_diameters{
+            let in_group_pairs: Vec<(usize, usize, f64)> = self.similar_pairs.iter()
+                .filter(|(a, b, _)| group_of[*a].is_some() && group_of[*a] == group_of[*b])
+                .copied()
+                .collect();
+            let adjacency = build_adjacency(account_amount, &in_group_pairs);
+            self.diameters = leaders.par_iter()
+                .filter_map(|leader| calc_group_diameter(&adjacency, &index_map[leader]).map(|diameter| (*leader, diameter)))
+                .collect();
+        }
+        if b_modularity{
+            let mut next_community = leaders.len();
+            let communities: Vec<usize> = group_of.iter().map(|group| group.unwrap_or_else(|| { next_community += 1; next_community - 1 })).collect();
+            let community_modularity = calc_community_modularity(&communities, &self.similar_pairs);
+            self.modularity = Some(community_modularity.iter().sum());
+            self.community_modularity = leaders.iter().zip(community_modularity).map(|(leader, modularity)| (*leader, modularity)).collect();
+        }
+    }
+}
+
+/// # Description
+/// * 一个账号组: 组长、全部组员(组长位于首位，其余按与组长的相似度从高到低排列)以及组内相似度的最小值、平均值、最大值。
+/// * One account group: the leader, all members (the leader first, the others sorted by similarity to the leader from high to low), and the min, mean and max intra-group similarity.
+/// # Cohesion
+/// * 组员数量不超过COHESION_EXACT_GROUP_SIZE_LIMIT时统计全部组员对，否则仅统计各组员与组长之间的相似度，cohesion_pair_amount为参与统计的账号对数量。
+/// * Up to COHESION_EXACT_GROUP_SIZE_LIMIT members every member pair is used, larger groups only use the similarity of each member to the leader. cohesion_pair_amount is the number of pairs used.
+/// # Size
+/// * size()为不同账号名的数量，occurrence_count为全部组员出现次数之和。
+/// * size() is the amount of distinct account names, occurrence_count is the sum of the occurrence counts of all members.
+/// # Modularity
+/// * 使用社区发现策略时，modularity为该社区对整体模块度的贡献，全部社区之和即为CGroupingResult::modularity；其他策略下为None。
+/// * With the community strategy, modularity is the contribution of the community to the overall modularity, and all of them add up to CGroupingResult::modularity. It is None for other strategies.
+/// * 精修或重选组长后，按最终的分组重新计算。
+/// * After refinement or leader reelection it is recalculated from the final groups.
+/// # Diameter
+/// * 使用连通分量策略时，diameter为组内任意两账号之间最短相似链的最大跳数，数值较大说明组内存在过度链接；组长模式下为None。
+/// * With the connected components strategy, diameter is the largest hop count of the shortest similarity chain between any two members.
+///   A large value reveals over-chaining. It is None in leader mode.
+/// * 精修或重选组长后，按相似账号对在最终组内构成的图重新计算，组在该图中不连通时为None。
+/// * After refinement or leader reelection it is recalculated on the graph the similar pairs form inside the final group, and it is None if the group is not connected in that graph.
+/// * 组员数量不超过DIAMETER_EXACT_GROUP_SIZE_LIMIT时为精确值；超过时为两次广度优先搜索得到的估计值，可能小于真实直径。
+/// * It is exact up to DIAMETER_EXACT_GROUP_SIZE_LIMIT members. Larger groups get an estimate from two breadth-first searches, which may be lower than the true diameter.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CAccountGroup{
+    pub leader: String,
+    pub members: Vec<CGroupMember>,
+    pub occurrence_count: u64,
+    pub diameter: Option<usize>,
+    pub modularity: Option<f64>,
+    pub min_similarity: f64,
+    pub mean_similarity: f64,
+    pub max_similarity: f64,
+    pub cohesion_pair_amount: usize
+}
+impl CAccountGroup{
+    pub fn size(&self) -> usize{
+        self.members.len()
+    }
+
+    /// 全部组员的账号名，组长位于首位
+    ///
+    /// The account names of all members, the leader first
+    pub fn member_names(&self) -> Vec<&str>{
+        self.members.iter().map(|m| m.account_name.as_str()).collect()
+    }
+}
+
+/// # Description
+/// * 一次完整分组的结果，包括分组所用的参数以及全部账号组。
+/// * The result of a complete grouping run, including the parameters used and all account groups.
+/// # Stable form
+/// * 账号组按组员数量从多到少排序，数量相同时按组长名排序，因此相同的分组内容总会得到相同的序列化结果。
+/// * Groups are sorted by member amount from most to least, and ties are sorted by leader name, so the same grouping always serializes to the same output.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CGroupingResult{
+    pub format_version: u32,
+    pub threshold: CSimilarityGroupingThreshold,
+    pub options: CGroupingOptions,          // 分组所用的选项，不含只影响运行效率的线程数量(记为0)
+    pub account_amount: usize,              // 参与分组的(去重后的)账号数量
+    pub total_occurrences: u64,             // 参与分组的账号出现次数之和
+    pub groups: Vec<CAccountGroup>,
+    pub noise_accounts: Vec<String>,        // 密度聚类中不属于任何簇的噪声账号，按账号名排序；其他策略下为空
+    pub modularity: Option<f64>,            // 社区发现的模块度(含被threshold_group_members过滤掉的社区，精修或重选组长后按最终分组计算)；其他策略下为None
+    pub refinement: Option<CRefinementReport>,                  // 分组精修的统计，未启用时为None
+    pub leader_reelection: Option<CLeaderReelectionReport>,     // 组长重选的统计，未启用时为None
+    pub min_hash_lsh: Option<CMinHashLshReport>                 // MinHash LSH分组的统计，其他策略下为None
+}
+impl CGroupingResult{
+
+    /// 账号组数量
+    ///
+    /// The amount of groups
+    pub fn group_amount(&self) -> usize{
+        self.groups.len()
+    }
+
+    /// 转换为与group_by_similarity*相同形式的账号组信息表，组序号即排序后的位置
+    ///
+    /// Convert to a group map in the same form as group_by_similarity*, the group index is the position after sorting
+    pub fn to_group_map(&self) -> HashMap<usize, Vec<String>>{
+        self.groups.iter()
+            .enumerate()
+            .map(|(index, group)| (index, group.members.iter().map(|m| m.account_name.clone()).collect()))
+            .collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl CGroupingResult {
+
+    /// 序列化为JSON字符串
+    ///
+    /// Serialize to a JSON string
+    pub fn to_json_string(&self) -> String{
+        serde_json::to_string(self).expect("grouping result is always serializable")
+    }
+
+    /// 从JSON字符串反序列化
+    ///
+    /// Deserialize from a JSON string
+    pub fn from_json_str(json_str: &str) -> serde_json::Result<CGroupingResult>{
+        serde_json::from_str(json_str)
+    }
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    /// 按指定的阈值与效率档位进行分组，返回包含组长、组员相似度、组内相似度统计及分组参数的完整分组结果
+    ///
+    /// Group the accounts with the given thresholds and efficiency mode, and return the complete grouping result
+    /// with leaders, member similarities, intra-group similarity stats and the parameters used
+    pub fn group_by_similarity_with_result(&self, threshold: &CSimilarityGroupingThreshold, mode: EfficiencyMode) -> CGroupingResult{
+        self.group_by_similarity_with_options(threshold, &CGroupingOptions{ mode, ..Default::default() })
+    }
+
+    /// 按指定的阈值与分组选项进行分组，返回完整的分组结果。启用确定性模式时，相同的输入与参数总会得到完全相同的结果，与线程数量及执行时序无关
+    ///
+    /// Group the accounts with the given thresholds and grouping options, and return the complete grouping result.
+    /// In deterministic mode the same input and parameters always give identical output, regardless of the thread count or timing
+    pub fn group_by_similarity_with_options(&self, threshold: &CSimilarityGroupingThreshold, options: &CGroupingOptions) -> CGroupingResult{
+        let mut threshold = threshold.clone();
+        let (mut index_map, mut annotations) = match options.strategy {
+            ClusteringStrategy::Leader => (self.group_account_indexes_by_similarity(&mut threshold, options), CClusteringAnnotations::default()),
+            ClusteringStrategy::ConnectedComponents => {
+                threshold.threshold_sim = threshold.threshold_sim.min(1.0);
+                self.group_account_indexes_by_connected_components(threshold.threshold_sim, &options.blocking_keys)
+            },
+            ClusteringStrategy::Density { min_points } => {
+                threshold.threshold_sim = threshold.threshold_sim.min(1.0);
+                self.group_account_indexes_by_density(threshold.threshold_sim, min_points, &options.blocking_keys)
+            },
+            ClusteringStrategy::Community { edge_floor } => self.group_account_indexes_by_communities(self.find_similar_index_pairs_by_blocking_keys(edge_floor, &options.blocking_keys)),
+            ClusteringStrategy::MinHashLsh(params) => {
+                threshold.threshold_sim = threshold.threshold_sim.min(1.0);
+                self.group_account_indexes_by_min_hash_lsh(threshold.threshold_sim, &params)
+            }
+        };
+        let mut refinement = None;
+        if options.refinement_rounds > 0{
+            let (refined_map, report) = self.refine_groups(index_map, &mut annotations, threshold.threshold_sim.min(1.0), options.refinement_rounds, &options.blocking_keys);
+            index_map = refined_map;
+            refinement = Some(report);
+        }
+        let mut leader_reelection = None;
+        if options.b_reelect_medoid_leader{
+            let (reelected_map, report) = self.reelect_medoid_leaders(index_map, &mut annotations, threshold.threshold_sim.min(1.0), options.leader_recheck_action, &options.blocking_keys);
+            index_map = reelected_map;
+            leader_reelection = Some(report);
+        }
+        let mut result = self.generate_grouping_result(index_map, &annotations, threshold, options.recorded());
+        result.refinement = refinement;
+        result.leader_reelection = leader_reelection;
+        result
+    }
+
+    /// 账号组的全部组员及其对应记录的载荷，顺序与group.members一致，按组员的account_index直接取回。未通过new_with_records初始化时载荷均为空
+    ///
+    /// All members of the account group with the payloads of their records, in the same order as group.members, looked up directly by each member's account_index.
+    /// The payloads are empty unless built with new_with_records
+    pub fn members_with_payloads<'g>(&self, group: &'g CAccountGroup) -> Vec<(&'g CGroupMember, &[&'a P])>{
+        group.members.iter()
+            .map(|member| (member, self.payloads(member.account_index)))
+            .collect()
+    }
+
+    // 由 组长序号 -> 组员序号 的映射生成完整的分组结果，过滤掉组员数量少于阈值的组
+    pub(crate) fn generate_grouping_result(&self, index_map: HashMap<usize, Vec<usize>>, annotations: &CClusteringAnnotations,
+                                           threshold: CSimilarityGroupingThreshold, options: CGroupingOptions) -> CGroupingResult{
+        let mut index_groups: Vec<(usize, Vec<usize>)> = index_map.into_iter()
+            .filter(|(_, members)| self.group_size(members, options.b_weighted_group_size) >= threshold.threshold_group_members as u64)
+            .collect();
+        index_groups.sort_by_key(|group| group.0);
+        let mut groups: Vec<CAccountGroup> = index_groups.par_iter()
+            .map(|(leader, members)| self.generate_account_group(*leader, members, annotations))
+            .collect();
+        groups.sort_by(|a, b| b.size().cmp(&a.size()).then_with(|| a.leader.cmp(&b.leader)));
+        let mut noise_accounts: Vec<String> = annotations.noise.iter().map(|index| self.account_name(*index).to_string()).collect();
+        noise_accounts.sort();
+        CGroupingResult{
+            format_version: GROUPING_RESULT_FORMAT_VERSION,
+            threshold,
+            options,
+            account_amount: self.data_vec_size,
+            total_occurrences: self.total_occurrences(),
+            groups,
+            noise_accounts,
+            modularity: annotations.modularity,
+            refinement: None,
+            leader_reelection: None,
+            min_hash_lsh: annotations.min_hash_lsh.clone()
+        }
+    }
+
+    // 生成单个账号组的详细信息
+    fn generate_account_group(&self, leader: usize, member_indexes: &[usize], annotations: &CClusteringAnnotations) -> CAccountGroup{
+        let leader_obj = &self.analyse_obj(leader);
+        let mut members: Vec<(usize, f64)> = member_indexes.iter()
+            .filter(|index| **index != leader)
+            .map(|index| (*index, leader_obj.calc_similarity(&self.analyse_obj(*index)).0))
+            .collect();
+        members.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal)
+            .then_with(|| self.account_name(a.0).cmp(self.account_name(b.0))));
+
+        // 组内相似度统计
+        let similarities: Vec<f64> = if member_indexes.len() <= COHESION_EXACT_GROUP_SIZE_LIMIT {
+            let mut all_indexes = vec![leader];
+            all_indexes.extend(members.iter().map(|m| m.0));
+            let mut similarities = members.iter().map(|m| m.1).collect::<Vec<f64>>();
+            for (position, index_a) in all_indexes.iter().enumerate().skip(1){
+                for index_b in &all_indexes[position + 1..]{
+                    similarities.push(self.analyse_obj(*index_a).calc_similarity(&self.analyse_obj(*index_b)).0);
+                }
+            }
+            similarities
+        }else {
+            members.iter().map(|m| m.1).collect()
+        };
+        let (min_similarity, mean_similarity, max_similarity) = if similarities.is_empty() {
+            (1.0, 1.0, 1.0)
+        }else {
+            (similarities.iter().cloned().fold(f64::INFINITY, f64::min),
+             similarities.iter().sum::<f64>() / similarities.len() as f64,
+             similarities.iter().cloned().fold(f64::NEG_INFINITY, f64::max))
+        };
+
+        let mut group_members = vec![CGroupMember{
+            account_name: leader_obj.account_name.to_string(),
+            account_index: leader,
+            similarity_to_leader: 1.0,
+            occurrence_count: self.occurrence_count(leader),
+            density_role: annotations.density_roles.get(&leader).copied()
+        }];
+        group_members.extend(members.iter().map(|(index, similarity)| CGroupMember{
+            account_name: self.account_name(*index).to_string(),
+            account_index: *index,
+            similarity_to_leader: *similarity,
+            occurrence_count: self.occurrence_count(*index),
+            density_role: annotations.density_roles.get(index).copied()
+        }));
+        CAccountGroup{
+            leader: leader_obj.account_name.to_string(),
+            occurrence_count: group_members.iter().map(|m| m.occurrence_count).sum(),
+            diameter: annotations.diameters.get(&leader).copied(),
+            modularity: annotations.community_modularity.get(&leader).copied(),
+            members: group_members,
+            min_similarity,
+            mean_similarity,
+            max_similarity,
+            cohesion_pair_amount: similarities.len()
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leader_reelection::LeaderRecheckAction;
+    #[test]
+    fn it_works() {
+        let account_vec = vec!["ubut2739", "ubut1179", "ubut2222", "zz2871369", "zz2871370", "htgt4303", "a1f6", "a1f55"];
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.8, threshold_group_members: 2 };
+        let result = ana.group_by_similarity_with_result(&threshold, EfficiencyMode::Accurately);
+        assert_eq!(result.threshold, threshold);
+        assert_eq!(result.options, CGroupingOptions{ mode: EfficiencyMode::Accurately, strategy: ClusteringStrategy::Leader, b_deterministic: false, b_weighted_group_size: false,
+            refinement_rounds: 0, b_reelect_medoid_leader: false, leader_recheck_action: LeaderRecheckAction::Keep, blocking_keys: Vec::new(), thread_amount: 0, group_granularity: 0 });
+        assert_eq!(result.total_occurrences, account_vec.len() as u64);
+        assert_eq!(result.account_amount, account_vec.len());
+        assert!(result.groups.windows(2).all(|w| w[0].size() > w[1].size() || (w[0].size() == w[1].size() && w[0].leader < w[1].leader)));
+        assert_eq!(result.groups.iter().map(|g| g.size()).sum::<usize>(),
+                   ana.group_by_similarity_accurately(0.8, 2).values().map(|g| g.len()).sum::<usize>());
+        assert_eq!(result.to_group_map().len(), result.group_amount());
+
+        for group in result.groups.iter(){
+            assert_eq!(group.members[0].account_name, group.leader);
+            assert!(group.members[1..].iter().all(|m| m.similarity_to_leader >= 0.8));
+            assert!(group.members[1..].windows(2).all(|w| w[0].similarity_to_leader >= w[1].similarity_to_leader));
+            assert_eq!(group.cohesion_pair_amount, group.size() * (group.size() - 1) / 2);
+            assert!(group.min_similarity <= group.mean_similarity && group.mean_similarity <= group.max_similarity);
+            assert_eq!(group.occurrence_count, group.size() as u64);
+            assert_eq!(group.diameter, None);
+        }
+        let ubut_group = result.groups.iter().find(|g| g.member_names().contains(&"ubut2739")).unwrap();
+        assert_eq!(ubut_group.size(), 3);
+
+        // 单个账号的组
+        let singles = ana.group_by_similarity_with_result(&CSimilarityGroupingThreshold{ threshold_sim: 1.0, threshold_group_members: 1 }, EfficiencyMode::Quickly);
+        assert_eq!(singles.group_amount(), account_vec.len());
+        assert!(singles.groups.iter().all(|g| g.cohesion_pair_amount == 0 && g.min_similarity == 1.0));
+
+        #[cfg(feature = "serde")]
+        {
+            let json = result.to_json_string();
+            assert_eq!(CGroupingResult::from_json_str(&json).unwrap(), result);
+            assert!(json.starts_with("{\"format_version\":1,\"threshold\":{\"threshold_sim\":0.8,\"threshold_group_members\":2},\"options\":{\"mode\":\"Accurately\",\"strategy\":\"Leader\",\"b_deterministic\":false,\"b_weighted_group_size\":false,\"refinement_rounds\":0,\"b_reelect_medoid_leader\":false,\"leader_recheck_action\":\"Keep\",\"blocking_keys\":[],\"thread_amount\":0,\"group_granularity\":0}"));
+            println!("{}", json);
+        }
+        // 出现次数: 按加权大小过滤时，不同账号名较少但出现次数多的组得以保留
+        let account_counts = vec![("ubut2739", 40), ("ubut1179", 2), ("zz2871369", 1), ("zz2871370", 1), ("zz2871371", 1), ("ubut2739", 10)];
+        let ana = CAccountNameAnaVec::new_with_counts(&account_counts);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.8, threshold_group_members: 3 };
+        let distinct = ana.group_by_similarity_with_result(&threshold, EfficiencyMode::Accurately);
+        assert_eq!(distinct.total_occurrences, 55);
+        assert_eq!(distinct.groups.iter().map(|g| g.leader.as_str()).collect::<Vec<&str>>(), vec!["zz2871369"]);
+        let weighted = ana.group_by_similarity_with_options(&threshold, CGroupingOptions::default().set_weighted_group_size(true));
+        assert_eq!(weighted.group_amount(), 2);
+        let ubut_group = weighted.groups.iter().find(|g| g.member_names().contains(&"ubut2739")).unwrap();
+        assert_eq!((ubut_group.size(), ubut_group.occurrence_count), (2, 52));
+        assert_eq!(ubut_group.members.iter().find(|m| m.account_name == "ubut2739").unwrap().occurrence_count, 50);
+
+        // 载荷: 同名的两个用户合并为一个账号名，二者的用户ID都可随组员取回
+        let records = vec![("ubut2739", 1001u64), ("ubut1179", 1002), ("ubut2739", 1003), ("zz2871369", 1004)];
+        let ana = CAccountNameAnaVec::new_with_records(&records);
+        let result = ana.group_by_similarity_with_result(&CSimilarityGroupingThreshold{ threshold_sim: 0.8, threshold_group_members: 2 }, EfficiencyMode::Accurately);
+        assert_eq!(result.group_amount(), 1);
+        let mut payloads: Vec<(&str, Vec<u64>)> = ana.members_with_payloads(&result.groups[0]).into_iter()
+            .map(|(m, member_payloads)| (m.account_name.as_str(), member_payloads.iter().map(|payload| **payload).collect()))
+            .collect();
+        payloads.sort();
+        assert_eq!(payloads, vec![("ubut1179", vec![1002]), ("ubut2739", vec![1001, 1003])]);
+        assert_eq!(result.groups[0].occurrence_count, 3);
+        assert!(result.groups[0].members.iter().all(|m| ana.to_vec()[m.account_index] == m.account_name && ana.occurrence_count(m.account_index) == m.occurrence_count));
+
+        // 连通分量策略: 任意相似账号对都位于同一组，链状相似的账号不会被拆开
+        let account_list = std::fs::read_to_string("test_data/test_account_list.txt").unwrap();
+        let account_vec: Vec<&str> = account_list.lines().take(500).collect();
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let options = CGroupingOptions::default().set_strategy(ClusteringStrategy::ConnectedComponents).clone();
+        let components = ana.group_by_similarity_with_options(&CSimilarityGroupingThreshold{ threshold_sim: 0.85, threshold_group_members: 1 }, &options);
+        assert_eq!(components.groups.iter().map(|g| g.size()).sum::<usize>(), ana.to_vec().len());
+        let group_of: HashMap<&str, usize> = components.groups.iter().enumerate()
+            .flat_map(|(index, group)| group.members.iter().map(move |m| (m.account_name.as_str(), index)))
+            .collect();
+        let names = ana.to_vec();
+        for (a, b, _) in ana.find_similar_index_pairs(0.85){
+            assert_eq!(group_of[names[a].as_str()], group_of[names[b].as_str()]);
+        }
+        assert!(components.groups.iter().all(|g| g.diameter.is_some() && (g.size() == 1) == (g.diameter == Some(0))));
+        assert!(components.groups.iter().any(|g| g.diameter.unwrap() >= 2));
+
+        // 确定性模式: 多次运行、不同线程数量下结果完全一致
+        // 分割粒度为200时1000个账号名走大数据量分支，且数据块数量多于或少于线程数量时分别使用线程池与独立线程
+        let account_vec: Vec<&str> = account_list.lines().take(1000).collect();
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.82, threshold_group_members: 2 };
+        let options_of = |thread_amount: usize| CGroupingOptions::default().set_mode(EfficiencyMode::Rapidly).set_deterministic(true)
+            .set_group_granularity(200).set_thread_amount(thread_amount).clone();
+        let expected = ana.group_by_similarity_with_options(&threshold, &options_of(1));
+        assert!(expected.group_amount() > 1);
+        assert_eq!(expected.options.thread_amount, 0);
+        for thread_amount in [1, 3, 64]{
+            let result = ana.group_by_similarity_with_options(&threshold, &options_of(thread_amount));
+            #[cfg(feature = "serde")]
+            assert_eq!(result.to_json_string().into_bytes(), expected.to_json_string().into_bytes());
+            assert_eq!(result, expected);
+        }
+        println!("{:?}", result);
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+use crate::connected_components::CUnionFind;
+
+
+// 簇间相似度的计算方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LinkageMethod {
+    #[default]
+    Average,        // 平均链接: 两簇间全部账号对相似度的平均值
+    Complete        // 全链接: 两簇间全部账号对相似度的最小值
+}
+
+// 树状图中的一次合并。节点序号0..n为叶子(即names中的账号)，n + i为第i次合并产生的节点
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CDendrogramMerge{
+    pub left: usize,
+    pub right: usize,
+    pub similarity: f64,        // 合并时两簇间的相似度
+    pub size: usize             // 合并后的账号数量
+}
+
+/// # Description
+/// * 层次聚类的树状图，合并按相似度从高到低排列，可在任意相似度处切分得到账号组。
+/// * The dendrogram of a hierarchical clustering. Merges are sorted by similarity from high to low, and the tree can be cut at any similarity level to get account groups.
+/// # Export
+/// * 支持导出为Newick(分支长度为相似度之差)或嵌套的JSON，便于可视化。
+/// * Can be exported as Newick (branch lengths are similarity differences) or nested JSON for visualization.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CDendrogram{
+    pub names: Vec<String>,
+    pub linkage: LinkageMethod,
+    pub merges: Vec<CDendrogramMerge>
+}
+impl CDendrogram{
+
+    /// 在指定的相似度处切分，合并相似度高于等于threshold_sim的簇。
+    /// 返回各组的账号名(组内按names中的顺序)，组按账号数量从多到少排列，数量相同时按首个账号名排序
+    ///
+    /// Cut the tree at the given similarity, joining the clusters whose merge similarity reaches threshold_sim.
+    /// Returns the account names of every group (in the order of names), groups are sorted by size from large to small, ties by their first account name
+    pub fn cut(&self, threshold_sim: f64) -> Vec<Vec<String>>{
+        let leaf_amount = self.names.len();
+        let mut union_find = CUnionFind::new(leaf_amount + self.merges.len());
+        for (index, merge) in self.merges.iter().enumerate(){
+            if merge.similarity >= threshold_sim{
+                union_find.union(merge.left, leaf_amount + index);
+                union_find.union(merge.right, leaf_amount + index);
+            }
+        }
+        let mut group_map: HashMap<usize, Vec<String>> = HashMap::new();
+        for (index, name) in self.names.iter().enumerate(){
+            group_map.entry(union_find.find(index)).or_default().push(name.clone());
+        }
+        let mut groups: Vec<Vec<String>> = group_map.into_values().collect();
+        groups.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(&b[0])));
+        groups
+    }
+
+    /// 导出为Newick格式，节点高度为1减去合并相似度，叶子名称使用单引号包裹
+    ///
+    /// Export in Newick format. The height of a node is 1 minus its merge similarity, and leaf names are wrapped in single quotes
+    pub fn to_newick(&self) -> String{
+        let mut newick = String::new();
+        if let Some(root) = self.root(){
+            self.write_newick_node(root, &mut newick);
+        }
+        newick.push(';');
+        newick
+    }
+
+    /// 导出为嵌套的JSON，叶子为{"name"}，合并节点为{"similarity", "size", "children"}
+    ///
+    /// Export as nested JSON. Leaves are {"name"} and merge nodes are {"similarity", "size", "children"}
+    pub fn to_json(&self) -> String{
+        match self.root() {
+            Some(root) => self.json_node(root).to_string(),
+            None => Value::Null.to_string()
+        }
+    }
+
+    // 根节点序号，没有账号时为None
+    fn root(&self) -> Option<usize>{
+        match self.names.len() {
+            0 => None,
+            leaf_amount => Some(leaf_amount + self.merges.len() - 1)
+        }
+    }
+
+    // 节点高度: 叶子为0，合并节点为1减去合并相似度
+    fn node_height(&self, node: usize) -> f64{
+        if node < self.names.len() { 0.0 } else { 1.0 - self.merges[node - self.names.len()].similarity }
+    }
+
+    fn write_newick_node(&self, node: usize, newick: &mut String){
+        if node < self.names.len(){
+            newick.push_str(&format!("'{}'", self.names[node].replace('\'', "''")));
+            return;
+        }
+        let merge = &self.merges[node - self.names.len()];
+        newick.push('(');
+        for (position, child) in [merge.left, merge.right].iter().enumerate(){
+            if position > 0{
+                newick.push(',');
+            }
+            self.write_newick_node(*child, newick);
+            newick.push_str(&format!(":{}", self.node_height(node) - self.node_height(*child)));
+        }
+        newick.push(')');
+    }
+
+    fn json_node(&self, node: usize) -> Value{
+        if node < self.names.len(){
+            return json!({ "name": self.names[node] });
+        }
+        let merge = &self.merges[node - self.names.len()];
+        json!({
+            "similarity": merge.similarity,
+            "size": merge.size,
+            "children": [self.json_node(merge.left), self.json_node(merge.right)]
+        })
+    }
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    /// 对全部账号进行层次聚类，返回树状图。需要计算两两之间的相似度，仅适用于数千条规模的账号集合
+    ///
+    /// Run hierarchical agglomerative clustering over all accounts and return the dendrogram.
+    /// Every pair is scored, so it is only suitable for sets of a few thousand accounts
+    pub fn calc_dendrogram(&self, linkage: LinkageMethod) -> CDendrogram{
+        self.calc_dendrogram_by_indexes(&(0..self.data_vec_size).collect::<Vec<usize>>(), linkage)
+    }
+
+    /// 对指定的账号子集进行层次聚类，返回树状图。不在集合中的账号名会被忽略
+    ///
+    /// Run hierarchical agglomerative clustering over the given subset of accounts and return the dendrogram. Names that are not in the collection are ignored
+    pub fn calc_dendrogram_of_subset(&self, account_names: &[&str], linkage: LinkageMethod) -> CDendrogram{
+        let index_table: HashMap<&str, usize> = (0..self.data_vec_size).map(|index| (self.account_name(index), index)).collect();
+        let mut indexes: Vec<usize> = account_names.iter().filter_map(|name| index_table.get(name).copied()).collect();
+        indexes.sort_unstable();
+        indexes.dedup();
+        self.calc_dendrogram_by_indexes(&indexes, linkage)
+    }
+
+    // 最近邻链算法，平均链接与全链接均满足可约性，因此结果与朴素算法一致，时间复杂度O(n^2)
+    fn calc_dendrogram_by_indexes(&self, indexes: &[usize], linkage: LinkageMethod) -> CDendrogram{
+        let size = indexes.len();
+        // 每个无序账号对只计算一次
+        let rows: Vec<Vec<f64>> = (0..size).into_par_iter()
+            .map(|row| ((row + 1)..size)
+                .map(|col| self.analyse_obj(indexes[row]).calc_similarity(&self.analyse_obj(indexes[col])).0)
+                .collect())
+            .collect();
+        let mut similarity_matrix = vec![1.0; size * size];
+        for (row, row_values) in rows.iter().enumerate(){
+            for (offset, similarity) in row_values.iter().enumerate(){
+                let col = row + 1 + offset;
+                similarity_matrix[row * size + col] = *similarity;
+                similarity_matrix[col * size + row] = *similarity;
+            }
+        }
+        let mut cluster_sizes = vec![1usize; size];
+        let mut active = vec![true; size];
+        let mut raw_merges: Vec<(usize, usize, f64)> = Vec::new();
+        let mut chain: Vec<usize> = Vec::new();
+        for _ in 1..size{
+            if chain.is_empty(){
+                chain.push(active.iter().position(|b_active| *b_active).unwrap());
+            }
+            loop {
+                let current = *chain.last().unwrap();
+                let previous = if chain.len() >= 2 { Some(chain[chain.len() - 2]) } else { None };
+                // 最相似的簇，相似度相同时优先取链上的前一个簇，其次取序号最小者
+                let mut nearest = previous;
+                let mut nearest_similarity = previous.map(|p| similarity_matrix[current * size + p]).unwrap_or(f64::NEG_INFINITY);
+                for candidate in (0..size).filter(|c| active[*c] && *c != current){
+                    let similarity = similarity_matrix[current * size + candidate];
+                    if similarity > nearest_similarity{
+                        nearest = Some(candidate);
+                        nearest_similarity = similarity;
+                    }
+                }
+                let nearest = nearest.unwrap();
+                if Some(nearest) == previous{
+                    chain.truncate(chain.len() - 2);
+                    let (kept, removed) = (current.min(nearest), current.max(nearest));
+                    raw_merges.push((kept, removed, nearest_similarity));
+                    // Lance-Williams更新，合并后的簇保存在kept处
+                    for other in (0..size).filter(|o| active[*o] && *o != kept && *o != removed){
+                        let (similarity_kept, similarity_removed) = (similarity_matrix[kept * size + other], similarity_matrix[removed * size + other]);
+                        let merged = match linkage {
+                            LinkageMethod::Average => (similarity_kept * cluster_sizes[kept] as f64 + similarity_removed * cluster_sizes[removed] as f64) /
+                                (cluster_sizes[kept] + cluster_sizes[removed]) as f64,
+                            LinkageMethod::Complete => similarity_kept.min(similarity_removed)
+                        };
+                        similarity_matrix[kept * size + other] = merged;
+                        similarity_matrix[other * size + kept] = merged;
+                    }
+                    cluster_sizes[kept] += cluster_sizes[removed];
+                    active[removed] = false;
+                    break;
+                }
+                chain.push(nearest);
+            }
+        }
+
+        // 按相似度从高到低排列合并，并将矩阵中的位置换算为树状图的节点序号
+        raw_merges.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+        let mut union_find = CUnionFind::new(size);
+        let mut node_of_root: Vec<usize> = (0..size).collect();
+        let mut size_of_root = vec![1usize; size];
+        let mut merges: Vec<CDendrogramMerge> = Vec::with_capacity(raw_merges.len());
+        for (a, b, similarity) in raw_merges{
+            let (root_a, root_b) = (union_find.find(a), union_find.find(b));
+            let (node_a, node_b) = (node_of_root[root_a], node_of_root[root_b]);
+            let merged_size = size_of_root[root_a] + size_of_root[root_b];
+            union_find.union(root_a, root_b);
+            let root = union_find.find(root_a);
+            node_of_root[root] = size + merges.len();
+            size_of_root[root] = merged_size;
+            merges.push(CDendrogramMerge{ left: node_a.min(node_b), right: node_a.max(node_b), similarity, size: merged_size });
+        }
+        CDendrogram{
+            names: indexes.iter().map(|index| self.account_name(*index).to_string()).collect(),
+            linkage,
+            merges
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze_account_name_similarity::CAccountNameSimAnalyse;
+    #[test]
+    fn it_works() {
+        let account_vec = vec!["ubut2739", "ubut1179", "ubut2222", "ubut27391", "zz2871369", "zz2871370", "htgt4303", "a'b"];
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        for linkage in [LinkageMethod::Average, LinkageMethod::Complete]{
+            let dendrogram = ana.calc_dendrogram(linkage);
+            assert_eq!(dendrogram.merges.len(), account_vec.len() - 1);
+            assert_eq!(dendrogram.merges.last().unwrap().size, account_vec.len());
+            assert!(dendrogram.merges.windows(2).all(|w| w[0].similarity >= w[1].similarity));
+            assert_eq!(dendrogram.cut(1.1).len(), account_vec.len());
+            assert_eq!(dendrogram.cut(-1.0).len(), 1);
+            let groups = dendrogram.cut(0.8);
+            assert!(groups.iter().any(|g| g.contains(&"zz2871369".to_string()) && g.contains(&"zz2871370".to_string())));
+
+            let newick = dendrogram.to_newick();
+            assert!(newick.ends_with(");") && newick.contains("'a''b'"));
+            assert_eq!(newick.matches('(').count(), account_vec.len() - 1);
+            let json: Value = serde_json::from_str(&dendrogram.to_json()).unwrap();
+            assert_eq!(json["size"], json!(account_vec.len()));
+            println!("{}", newick);
+        }
+
+        // 两个账号的子集只有一次合并，相似度即两者的相似度；不在集合中的账号被忽略
+        let subset = ana.calc_dendrogram_of_subset(&["zz2871370", "ubut2739", "unknown"], LinkageMethod::Complete);
+        assert_eq!(subset.names.len(), 2);
+        assert_eq!(subset.merges, vec![CDendrogramMerge{ left: 0, right: 1, size: 2,
+            similarity: CAccountNameSimAnalyse::new("ubut2739").calc_similarity(&CAccountNameSimAnalyse::new("zz2871370")).0 }]);
+        assert_eq!(ana.calc_dendrogram_of_subset(&[], LinkageMethod::Average).to_newick(), ";");
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use rayon::prelude::*;
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+use crate::grouping_result::CClusteringAnnotations;
+use crate::blocking::BlockingKey;
+
+// 选取中心点时候选组员的最大数量，组员更多时按序号等间隔抽样，避免平方级的计算量
+const MEDOID_CANDIDATE_LIMIT: usize = 256;
+
+
+// 重选组长后，对与新组长相似度低于阈值的组员的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LeaderRecheckAction {
+    #[default]
+    Keep,       // 保留在原组
+    Eject,      // 移出原组，单独成组
+    Move        // 移入与其最相似且达到阈值的其他组(按新组长判断)，没有这样的组时单独成组
+}
+
+// 组长重选的统计
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CLeaderReelectionReport{
+    pub leader_changes: usize,          // 组长发生变化的组数量
+    pub below_threshold: usize,         // 与新组长相似度低于阈值的组员数量
+    pub moved: usize,                   // 移入其他组的组员数量
+    pub ejected: usize                  // 移出后单独成组的组员数量
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    // 将各组的组长重选为中心点(与其他组员相似度之和最大的组员，相同时取序号最小者)，再按action处理与新组长相似度低于阈值的组员
+    // 组员数量超过MEDOID_CANDIDATE_LIMIT时，仅在按序号等间隔抽取的MEDOID_CANDIDATE_LIMIT个以内的组员中选取中心点，但仍与全部组员计算相似度之和
+    // 移入其他组时，组员只与至少在一个分块键下处于同一块的组长比较，blocking_keys为空时按骨架分块
+    // 完成后按最终的分组重新计算annotations中以组长为键的直径及模块度
+    pub(crate) fn reelect_medoid_leaders(&self, index_map: HashMap<usize, Vec<usize>>, annotations: &mut CClusteringAnnotations,
+                                         threshold_sim: f64, action: LeaderRecheckAction, blocking_keys: &[BlockingKey]) -> (HashMap<usize, Vec<usize>>, CLeaderReelectionReport){
+        let mut groups: Vec<(usize, Vec<usize>)> = index_map.into_iter().collect();
+        groups.sort_by_key(|group| group.0);
+
+        // 重选组长，并找出与新组长相似度低于阈值的组员
+        let reelected: Vec<(usize, usize, Vec<usize>, Vec<usize>)> = groups.into_par_iter()
+            .map(|(old_leader, members)| {
+                let leader = self.find_medoid(&members);
+                let (kept, below): (Vec<usize>, Vec<usize>) = members.iter()
+                    .filter(|index| **index != leader)
+                    .partition(|index| self.analyse_obj(leader).calc_similarity_at_least(&self.analyse_obj(**index), threshold_sim).is_some());
+                let mut new_members = vec![leader];
+                new_members.extend(kept);
+                (old_leader, leader, new_members, below)
+            })
+            .collect();
+
+        let mut report = CLeaderReelectionReport::default();
+        let mut result: HashMap<usize, Vec<usize>> = HashMap::new();
+        let mut below_members: Vec<(usize, usize)> = Vec::new();
+        for (old_leader, leader, mut members, below) in reelected{
+            if leader != old_leader{
+                report.leader_changes += 1;
+            }
+            report.below_threshold += below.len();
+            match action {
+                LeaderRecheckAction::Keep => members.extend(below),
+                _ => below_members.extend(below.into_iter().map(|index| (index, leader)))
+            }
+            result.insert(leader, members);
+        }
+
+        // 移出或移入其他组，目标组只按重选后的组长判断，与处理顺序无关
+        let targets: Vec<Option<usize>> = match action {
+            LeaderRecheckAction::Move => {
+                let default_keys = [BlockingKey::default()];
+                let blocking_keys = if blocking_keys.is_empty() { &default_keys[..] } else { blocking_keys };
+                let mut leaders: Vec<usize> = result.keys().copied().collect();
+                leaders.sort_unstable();
+                let member_indexes: Vec<usize> = below_members.iter().map(|(index, _)| *index).collect();
+                let candidates = self.find_blocked_candidates(&leaders, &member_indexes, blocking_keys);
+                below_members.par_iter()
+                    .zip(candidates)
+                    .map(|((index, own_leader), candidate_leaders)| candidate_leaders.iter()
+                        .filter(|leader| *leader != own_leader)
+                        .filter_map(|leader| self.analyse_obj(*leader).calc_similarity_at_least(&self.analyse_obj(*index), threshold_sim)
+                            .map(|(similarity, _)| (*leader, similarity)))
+                        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| b.0.cmp(&a.0)))
+                        .map(|(leader, _)| leader))
+                    .collect()
+            },
+            _ => vec![None; below_members.len()]
+        };
+        for ((index, _), target) in below_members.into_iter().zip(targets){
+            match target {
+                Some(leader) => {
+                    report.moved += 1;
+                    result.get_mut(&leader).unwrap().push(index);
+                },
+                None => {
+                    report.ejected += 1;
+                    result.insert(index, vec![index]);
+                }
+            }
+        }
+        annotations.refresh_group_annotations(&result, self.data_vec_size);
+        (result, report)
+    }
+
+    // 组内的中心点: 与其他组员相似度之和最大的组员，相同时取序号最小者
+    // 组员数量超过MEDOID_CANDIDATE_LIMIT时，候选组员为按序号排序后等间隔抽取的样本，覆盖整个组而非只取序号最小的一段
+    pub(crate) fn find_medoid(&self, members: &[usize]) -> usize{
+        if members.len() <= 2{
+            return *members.iter().min().unwrap();
+        }
+        let mut sorted_members = members.to_vec();
+        sorted_members.sort_unstable();
+        let stride = sorted_members.len().div_ceil(MEDOID_CANDIDATE_LIMIT);
+        let candidates: Vec<usize> = sorted_members.into_iter().step_by(stride).collect();
+        candidates.iter()
+            .map(|candidate| (*candidate, members.iter()
+                .filter(|index| *index != candidate)
+                .map(|index| self.analyse_obj(*candidate).calc_similarity(&self.analyse_obj(*index)).0)
+                .sum::<f64>()))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| b.0.cmp(&a.0)))
+            .unwrap().0
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_account_name_by_similarity::{CSimilarityGroupingThreshold, CGroupingOptions, EfficiencyMode, ClusteringStrategy};
+    use crate::grouping_result::CAccountGroup;
+    use crate::connected_components::{build_adjacency, calc_group_diameter};
+    #[test]
+    fn it_works() {
+        let account_list = std::fs::read_to_string("test_data/test_account_list.txt").unwrap();
+        let account_vec: Vec<&str> = account_list.lines().take(300).collect();
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.85, threshold_group_members: 1 };
+        let mut options = CGroupingOptions::default().set_mode(EfficiencyMode::Accurately).set_reelect_medoid_leader(true).clone();
+        let names = ana.to_vec();
+        let name_index: HashMap<&str, usize> = names.iter().enumerate().map(|(index, name)| (name.as_str(), index)).collect();
+
+        for action in [LeaderRecheckAction::Keep, LeaderRecheckAction::Eject, LeaderRecheckAction::Move]{
+            options.set_leader_recheck_action(action);
+            let result = ana.group_by_similarity_with_options(&threshold, &options);
+            let report = result.leader_reelection.clone().unwrap();
+            assert_eq!(result.groups.iter().map(|g| g.size()).sum::<usize>(), names.len());
+            assert!(report.leader_changes > 0);
+            assert_eq!(report.below_threshold, report.moved + report.ejected + if action == LeaderRecheckAction::Keep { report.below_threshold } else { 0 });
+            for group in result.groups.iter(){
+                let members: Vec<usize> = group.member_names().iter().map(|name| name_index[name]).collect();
+                if action == LeaderRecheckAction::Keep{
+                    // 组长为中心点
+                    assert_eq!(ana.find_medoid(&members), members[0]);
+                }else {
+                    assert!(group.members[1..].iter().all(|m| m.similarity_to_leader >= threshold.threshold_sim));
+                }
+            }
+        }
+        assert_eq!(ana.group_by_similarity_with_options(&threshold, CGroupingOptions::default().set_mode(EfficiencyMode::Accurately)).leader_reelection, None);
+
+        // 组员数量超过MEDOID_CANDIDATE_LIMIT时按序号等间隔抽样，序号靠后的紧密子群中的组员同样可以成为中心点
+        let large_group_names: Vec<String> = (0..MEDOID_CANDIDATE_LIMIT + 44).map(|i| format!("a{}{}", "q".repeat(i % 13), i * 7919 % 100003))
+            .chain((0..400).map(|i| format!("zzcluster{:04}", i)))
+            .collect();
+        let large_group_refs: Vec<&str> = large_group_names.iter().map(|name| name.as_str()).collect();
+        let large_group_ana = CAccountNameAnaVec::new(&large_group_refs);
+        let medoid = large_group_ana.find_medoid(&(0..large_group_refs.len()).collect::<Vec<usize>>());
+        assert!(medoid >= MEDOID_CANDIDATE_LIMIT && large_group_ana.account_name(medoid).starts_with("zzcluster"));
+
+        // 连通分量重选组长并移出组员后，直径按缩小后的组重新计算，移出后单独成组的账号直径为0
+        let mut components_options = CGroupingOptions::default().set_strategy(ClusteringStrategy::ConnectedComponents).clone();
+        let components = ana.group_by_similarity_with_options(&threshold, &components_options);
+        let component_of: HashMap<&str, &CAccountGroup> = components.groups.iter()
+            .flat_map(|group| group.members.iter().map(move |m| (m.account_name.as_str(), group)))
+            .collect();
+        components_options.set_reelect_medoid_leader(true).set_leader_recheck_action(LeaderRecheckAction::Eject);
+        let ejected = ana.group_by_similarity_with_options(&threshold, &components_options);
+        assert!(ejected.leader_reelection.clone().unwrap().ejected > 0);
+        let similar_pairs = ana.find_similar_index_pairs(threshold.threshold_sim);
+        let mut shrunken_amount = 0;
+        for group in ejected.groups.iter(){
+            let members: Vec<usize> = group.member_names().iter().map(|name| name_index[name]).collect();
+            let in_group_pairs: Vec<(usize, usize, f64)> = similar_pairs.iter().filter(|(a, b, _)| members.contains(a) && members.contains(b)).copied().collect();
+            assert_eq!(group.diameter, calc_group_diameter(&build_adjacency(names.len(), &in_group_pairs), &members));
+            let component = component_of[group.leader.as_str()];
+            if group.size() < component.size(){
+                shrunken_amount += 1;
+                if group.size() == 1{
+                    assert_eq!(group.diameter, Some(0));
+                }else {
+                    assert!(group.diameter.unwrap() <= component.diameter.unwrap());
+                }
+            }
+        }
+        assert!(ejected.groups.iter().any(|group| group.size() > 1 && group.size() < component_of[group.leader.as_str()].size() &&
+            group.diameter.is_some() && group.diameter < component_of[group.leader.as_str()].diameter));
+        assert!(shrunken_amount > 0);
+    }
+}
@@ -1,14 +1,109 @@
 pub mod algorithm;
 pub mod analyze_account_name_similarity;
 pub mod group_account_name_by_similarity;
+pub mod calibration;
+pub mod weight_training;
+pub mod score_combiner;
+pub mod batch_scoring;
+pub mod similarity_matrix;
+pub mod grouping_result;
+pub mod explanation;
+pub mod feature_vector;
+pub mod pair_features;
+pub mod connected_components;
+pub mod density_clustering;
+pub mod hierarchical_clustering;
+pub mod community_detection;
+pub mod leader_reelection;
+pub mod refinement;
+pub mod blocking;
+pub mod min_hash_lsh;
 
 pub use crate::analyze_account_name_similarity::{CAccountNameSimAnalyse,
                                                  CAccountNameSimAnalyseParamsWeightTable,
-                                                 CAccountNameSimResultDetail};
+                                                 CAccountNameSimResultDetail,
+                                                 WeightTableError,
+                                                 NameComparison,
+                                                 CWeightTablePreset,
+                                                 WEIGHT_TABLE_PRESETS,
+                                                 DEFAULT_PARAMETER_WEIGHT_TABLE,
+                                                 UNIFORM_PARAMETER_WEIGHT_TABLE,
+                                                 SEQUENTIAL_COUNTER_PARAMETER_WEIGHT_TABLE,
+                                                 RANDOM_STRING_PARAMETER_WEIGHT_TABLE,
+                                                 EMAIL_PARAMETER_WEIGHT_TABLE};
 
 pub use crate::group_account_name_by_similarity::{CAccountNameAnaVec,
                                                   CSimilarityGroupingThreshold,
-                                                  EfficiencyMode};
+                                                  EfficiencyMode,
+                                                  ClusteringStrategy,
+                                                  CGroupingOptions};
+
+pub use crate::grouping_result::{CGroupingResult,
+                                 CAccountGroup,
+                                 CGroupMember,
+                                 GROUPING_RESULT_FORMAT_VERSION,
+                                 COHESION_EXACT_GROUP_SIZE_LIMIT,
+                                 DIAMETER_EXACT_GROUP_SIZE_LIMIT};
+
+pub use crate::density_clustering::DensityRole;
+
+pub use crate::hierarchical_clustering::{CDendrogram,
+                                         CDendrogramMerge,
+                                         LinkageMethod};
+
+pub use crate::leader_reelection::{CLeaderReelectionReport,
+                                   LeaderRecheckAction};
+
+pub use crate::refinement::CRefinementReport;
+
+pub use crate::blocking::BlockingKey;
+
+pub use crate::min_hash_lsh::{CMinHashLshIndex,
+                              CMinHashLshParams,
+                              CMinHashLshReport};
+
+pub use crate::explanation::{CSimilarityExplanation,
+                             CComponentExplanation};
+
+pub use crate::feature_vector::{CFeatureTable,
+                                FEATURE_COLUMN_NAMES,
+                                FEATURE_VECTOR_LEN,
+                                SKELETON_HASH_BUCKET_AMOUNT};
+
+pub use crate::pair_features::{CPairFeatureRow,
+                               CPairFeatureTable,
+                               PAIR_FEATURE_COLUMN_NAMES};
+
+pub use crate::calibration::{CSimilarityCalibrator,
+                             CalibrationMethod,
+                             calc_threshold_by_precision};
+
+pub use crate::weight_training::{TrainingObjective,
+                                 CWeightTableEvaluation,
+                                 CWeightTrainingReport,
+                                 train_weight_table,
+                                 train_weight_table_with_combiner,
+                                 evaluate_weight_table,
+                                 evaluate_weight_table_with_combiner,
+                                 generate_labeled_pairs_by_groups,
+                                 compare_weight_table_presets};
+
+pub use crate::score_combiner::{ScoreCombiner,
+                                TunableScoreCombiner,
+                                CScoreCombineContext,
+                                CScoreContribution,
+                                CEditDistanceJaroCombiner,
+                                CWeightedMeanCombiner,
+                                CGeometricMeanCombiner,
+                                CMaxCombiner,
+                                CLogisticCombiner};
+
+pub use crate::batch_scoring::{CBatchScoringOptions,
+                               CSimilarityMatch,
+                               find_similar_accounts_in_slice};
+
+pub use crate::similarity_matrix::{CDenseSimilarityMatrix,
+                                   CSparseSimilarityMatrix};
 
 
 #[cfg(test)]
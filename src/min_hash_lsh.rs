@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use rayon::prelude::*;
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+use crate::grouping_result::CClusteringAnnotations;
+
+
+// 生成各哈希函数种子所用的固定种子，保证相同的输入总会得到相同的索引
+const MIN_HASH_SEED: u64 = 0x5851_f42d_4c95_7f2d;
+
+/// # Description
+/// * MinHash LSH索引的参数。账号名(小写)被切分为长度为shingle_size的字符片段，每个账号计算bands * rows个MinHash值，
+///   每rows个值组成一个band，任一band完全相同的两个账号成为候选账号对。
+/// * Parameters of the MinHash LSH index. The lowercase account name is cut into character shingles of length shingle_size, and bands * rows MinHash values are computed for each account.
+///   Every rows values form a band, and two accounts become a candidate pair when any band of theirs is identical.
+/// # Recall and speed
+/// * bands越多、rows越少，候选账号对越多，召回率越高但速度越慢；反之则越快但会遗漏更多相似账号对。bands或rows为0时不产生候选账号对。
+/// * More bands and fewer rows give more candidate pairs, i.e. higher recall but slower grouping, and the other way round is faster but misses more similar pairs. No candidate pair is produced when bands or rows is 0.
+/// * recall_sample_size为估计召回率时抽样的账号数量，每个抽样账号与全部账号穷举比较，为0时不估计召回率。
+/// * recall_sample_size is the amount of accounts sampled to estimate the recall, each of which is compared with all accounts exhaustively. The recall is not estimated when it is 0.
+/// # Oversized buckets
+/// * 同一模板批量生成的账号(例如ubut0001 ~ ubut9999)会落入少数巨大的桶，桶内两两组合的数量随桶大小平方增长。
+///   账号数量超过max_bucket_size的桶按账号名排序，每个账号只与其后max_bucket_size - 1个账号组成候选账号对，候选账号对的数量不超过 bands * 账号数量 * (max_bucket_size - 1)。
+///   排序后相邻的账号仍相互连接，因此对按连通分量合并的分组影响很小。
+/// * Accounts generated from one template (e.g. ubut0001 ~ ubut9999) fall into a few huge buckets, where the amount of pairs grows with the square of the bucket size.
+///   Buckets holding more than max_bucket_size accounts are sorted by account name, and each account only pairs with the next max_bucket_size - 1 accounts, so there are at most bands * account amount * (max_bucket_size - 1) candidate pairs.
+///   Neighbours in the sorted order stay linked, so grouping by connected components is barely affected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CMinHashLshParams{
+    pub shingle_size: usize,            // 字符片段的长度，账号名短于该长度时整个账号名作为一个片段
+    pub bands: usize,                   // band数量
+    pub rows: usize,                    // 每个band的MinHash值数量
+    pub recall_sample_size: usize,      // 估计召回率时抽样的账号数量
+    pub max_bucket_size: usize          // 桶内两两组合的账号数量上限，超过时只组合排序后相邻的账号(小于2时按2计算)
+}
+impl Default for CMinHashLshParams{
+    fn default() -> CMinHashLshParams{
+        CMinHashLshParams{
+            shingle_size: 2,
+            bands: 24,
+            rows: 2,
+            recall_sample_size: 100,
+            max_bucket_size: 64
+        }
+    }
+}
+impl CMinHashLshParams{
+    pub fn set_shingle_size(&mut self, shingle_size: usize) -> &mut CMinHashLshParams {
+        self.shingle_size = shingle_size;
+        self
+    }
+    pub fn set_bands(&mut self, bands: usize) -> &mut CMinHashLshParams {
+        self.bands = bands;
+        self
+    }
+    pub fn set_rows(&mut self, rows: usize) -> &mut CMinHashLshParams {
+        self.rows = rows;
+        self
+    }
+    pub fn set_recall_sample_size(&mut self, recall_sample_size: usize) -> &mut CMinHashLshParams {
+        self.recall_sample_size = recall_sample_size;
+        self
+    }
+    pub fn set_max_bucket_size(&mut self, max_bucket_size: usize) -> &mut CMinHashLshParams {
+        self.max_bucket_size = max_bucket_size;
+        self
+    }
+}
+
+/// # Description
+/// * MinHash LSH分组的统计，包括候选账号对的数量以及与穷举比较相比的估计召回率。
+/// * Statistics of MinHash LSH grouping, including the amount of candidate pairs and the recall estimated against exhaustive comparison.
+/// # Estimated recall
+/// * 按序号等间隔抽取recall_sample_size个账号，与全部账号穷举比较得到相似度高于等于阈值的账号对，其中同时为候选账号对的比例即估计召回率。
+/// * recall_sample_size accounts are sampled at even index intervals and compared with all accounts exhaustively. The share of the resulting similar pairs that are also candidate pairs is the estimated recall.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CMinHashLshReport{
+    pub bucket_amount: usize,               // 含两个及以上账号的桶数量
+    pub oversized_bucket_amount: usize,     // 账号数量超过max_bucket_size的桶数量
+    pub candidate_pairs: usize,             // 去重后的候选账号对数量，即实际计算相似度的次数
+    pub exhaustive_pairs: u64,              // 穷举比较所需的账号对数量
+    pub similar_pairs: usize,               // 候选账号对中相似度高于等于阈值的数量
+    pub sampled_accounts: usize,            // 用于估计召回率的抽样账号数量
+    pub sampled_similar_pairs: usize,       // 抽样账号穷举比较得到的相似账号对数量
+    pub estimated_recall: Option<f64>       // 估计召回率，未抽样或抽样中没有相似账号对时为None
+}
+
+/// # Description
+/// * 由CAccountNameAnaVec构建的MinHash LSH索引，账号序号与to_vec()的顺序一致。
+/// * A MinHash LSH index built from a CAccountNameAnaVec. Account indexes follow the order of to_vec().
+/// # Memory
+/// * 候选账号按账号逐个生成并去重，不会先收集各band产生的重复账号对，内存占用只与去重后的候选账号对数量有关。
+/// * Candidates are generated and deduplicated account by account, so the duplicate pairs produced by different bands are never collected and memory only depends on the amount of distinct candidate pairs.
+pub struct CMinHashLshIndex{
+    params: CMinHashLshParams,
+    buckets: Vec<Vec<usize>>,                   // 含两个及以上账号的桶，桶内账号序号升序，超过max_bucket_size的桶按账号名排序
+    account_buckets: Vec<Vec<(usize, usize)>>   // 各账号所在的(桶序号, 在桶内的位置)
+}
+impl CMinHashLshIndex{
+
+    /// 构建索引所用的参数
+    ///
+    /// The parameters used to build the index
+    pub fn params(&self) -> &CMinHashLshParams{
+        &self.params
+    }
+
+    /// 含两个及以上账号的桶数量
+    ///
+    /// The amount of buckets holding two or more accounts
+    pub fn bucket_amount(&self) -> usize{
+        self.buckets.len()
+    }
+
+    /// 账号数量超过max_bucket_size的桶数量
+    ///
+    /// The amount of buckets holding more than max_bucket_size accounts
+    pub fn oversized_bucket_amount(&self) -> usize{
+        let max_bucket_size = self.max_bucket_size();
+        self.buckets.iter().filter(|bucket| bucket.len() > max_bucket_size).count()
+    }
+
+    /// 全部候选账号对(i < j)，去重后按(i, j)排序。超过max_bucket_size的桶只组合排序后相邻的账号(见CMinHashLshParams)
+    ///
+    /// All candidate pairs (i < j), deduplicated and sorted by (i, j). Buckets holding more than max_bucket_size accounts only pair neighbours in the sorted order (see CMinHashLshParams)
+    pub fn candidate_pairs(&self) -> Vec<(usize, usize)>{
+        (0..self.account_buckets.len()).into_par_iter()
+            .flat_map_iter(|index| self.later_candidates(index).into_iter().map(move |other| (index, other)))
+            .collect()
+    }
+
+    /// 与第index个账号组成候选账号对的全部账号，去重后按序号升序
+    ///
+    /// All accounts that form a candidate pair with the account at index, deduplicated and sorted by index
+    pub fn candidate_neighbours(&self, index: usize) -> Vec<usize>{
+        let max_bucket_size = self.max_bucket_size();
+        let mut neighbours: Vec<usize> = self.account_buckets[index].iter()
+            .flat_map(|(bucket, position)| {
+                let bucket = &self.buckets[*bucket];
+                bucket[position.saturating_sub(max_bucket_size - 1)..bucket.len().min(position.saturating_add(max_bucket_size))].iter().copied()
+            })
+            .filter(|other| *other != index)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    // 与第index个账号组成候选账号对且序号更大的账号，每个候选账号对只在序号较小的账号处出现一次
+    fn later_candidates(&self, index: usize) -> Vec<usize>{
+        let mut neighbours = self.candidate_neighbours(index);
+        neighbours.retain(|other| *other > index);
+        neighbours
+    }
+
+    fn max_bucket_size(&self) -> usize{
+        self.params.max_bucket_size.max(2)
+    }
+}
+
+// splitmix64混合函数
+fn mix64(mut x: u64) -> u64{
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+// 账号名(小写)各字符片段的哈希值(FNV-1a)
+fn hash_shingles(account_name: &str, shingle_size: usize) -> Vec<u64>{
+    let chars: Vec<char> = account_name.chars().flat_map(char::to_lowercase).collect();
+    let hash_chars = |shingle: &[char]| shingle.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, c| (hash ^ *c as u64).wrapping_mul(0x0000_0100_0000_01b3));
+    if chars.len() <= shingle_size{
+        return vec![hash_chars(&chars)];
+    }
+    chars.windows(shingle_size).map(hash_chars).collect()
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    /// 构建MinHash LSH索引，用于快速找出可能相似的候选账号对
+    ///
+    /// Build a MinHash LSH index to quickly find the candidate pairs that are likely to be similar
+    pub fn build_min_hash_lsh_index(&self, params: &CMinHashLshParams) -> CMinHashLshIndex{
+        let (bands, rows) = (params.bands, params.rows);
+        if bands == 0 || rows == 0{
+            return CMinHashLshIndex{ params: *params, buckets: Vec::new(), account_buckets: vec![Vec::new(); self.data_vec_size] };
+        }
+        let seeds: Vec<u64> = (0..(bands * rows) as u64).map(|i| mix64(MIN_HASH_SEED.wrapping_add(i))).collect();
+        // 每个账号各band的哈希值
+        let band_hashes: Vec<Vec<u64>> = (0..self.data_vec_size).into_par_iter().map(|index| self.analyse_obj(index))
+            .map(|obj| {
+                let shingles = hash_shingles(obj.account_name, params.shingle_size.max(1));
+                let signature: Vec<u64> = seeds.iter().map(|seed| shingles.iter().map(|shingle| mix64(shingle ^ seed)).min().unwrap()).collect();
+                signature.chunks(rows)
+                    .enumerate()
+                    .map(|(band, values)| values.iter().fold(mix64(band as u64), |hash, value| mix64(hash ^ value)))
+                    .collect()
+            })
+            .collect();
+
+        let mut buckets: Vec<Vec<usize>> = (0..bands).into_par_iter()
+            .flat_map_iter(|band| {
+                let mut band_buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+                for (index, hashes) in band_hashes.iter().enumerate(){
+                    band_buckets.entry(hashes[band]).or_default().push(index);
+                }
+                band_buckets.into_values().filter(|bucket| bucket.len() > 1)
+            })
+            .collect();
+        // 超过上限的桶按账号名排序，使相邻组合的账号尽量相似
+        let max_bucket_size = params.max_bucket_size.max(2);
+        buckets.par_iter_mut()
+            .filter(|bucket| bucket.len() > max_bucket_size)
+            .for_each(|bucket| bucket.sort_by_key(|index| (self.account_name(*index), *index)));
+        buckets.par_sort_unstable();
+        let mut account_buckets: Vec<Vec<(usize, usize)>> = vec![Vec::new(); self.data_vec_size];
+        for (bucket_index, bucket) in buckets.iter().enumerate(){
+            for (position, index) in bucket.iter().enumerate(){
+                account_buckets[*index].push((bucket_index, position));
+            }
+        }
+        CMinHashLshIndex{ params: *params, buckets, account_buckets }
+    }
+
+    // MinHash LSH分组: 仅对候选账号对计算相似度，相似度高于等于阈值的账号对按连通分量合并为组(组长及直径同连通分量策略)
+    // 候选账号对按账号逐个生成后直接计算相似度，不保存全部候选账号对
+    // 返回 (组长序号 -> 组员序号(组长在首位) 的映射, 含直径及LSH统计的附加信息)
+    pub(crate) fn group_account_indexes_by_min_hash_lsh(&self, threshold_sim: f64, params: &CMinHashLshParams) -> (HashMap<usize, Vec<usize>>, CClusteringAnnotations){
+        let index = self.build_min_hash_lsh_index(params);
+        let (candidate_amounts, edge_lists): (Vec<usize>, Vec<Vec<_>>) = (0..self.data_vec_size).into_par_iter()
+            .map(|a| {
+                let candidates = index.later_candidates(a);
+                let edges = candidates.iter()
+                    .filter_map(|b| self.analyse_obj(a).calc_similarity_at_least(&self.analyse_obj(*b), threshold_sim)
+                        .map(|(similarity, _)| (a, *b, similarity)))
+                    .collect();
+                (candidates.len(), edges)
+            })
+            .unzip();
+        let candidate_pairs: usize = candidate_amounts.iter().sum();
+        let edges: Vec<(usize, usize, f64)> = edge_lists.into_iter().flatten().collect();
+        let similar_pairs = edges.len();
+        let (index_map, mut annotations) = self.group_account_indexes_by_similar_pairs(edges);
+
+        // 抽样估计召回率
+        let size = self.data_vec_size;
+        let sample_size = params.recall_sample_size.min(size);
+        let sampled: Vec<usize> = (0..sample_size).map(|i| i * size / sample_size).collect();
+        let (sampled_similar_pairs, sampled_found_pairs) = sampled.par_iter()
+            .map(|sample| {
+                let neighbours = index.candidate_neighbours(*sample);
+                (0..size)
+                    .filter(|other| *other != *sample)
+                    .filter(|other| self.analyse_obj(*sample).calc_similarity_at_least(&self.analyse_obj(*other), threshold_sim).is_some())
+                    .fold((0, 0), |(similar, found), other| (similar + 1, found + neighbours.binary_search(&other).is_ok() as usize))
+            })
+            .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+
+        annotations.min_hash_lsh = Some(CMinHashLshReport{
+            bucket_amount: index.bucket_amount(),
+            oversized_bucket_amount: index.oversized_bucket_amount(),
+            candidate_pairs,
+            exhaustive_pairs: size as u64 * (size as u64).saturating_sub(1) / 2,
+            similar_pairs,
+            sampled_accounts: sampled.len(),
+            sampled_similar_pairs,
+            estimated_recall: if sampled_similar_pairs > 0 { Some(sampled_found_pairs as f64 / sampled_similar_pairs as f64) } else { None }
+        });
+        (index_map, annotations)
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_account_name_by_similarity::{CSimilarityGroupingThreshold, CGroupingOptions, ClusteringStrategy};
+    #[test]
+    fn it_works() {
+        assert_eq!(hash_shingles("UBut", 2), hash_shingles("ubut", 2));
+        assert_eq!(hash_shingles("ubut", 2).len(), 3);
+        assert_eq!(hash_shingles("ub", 3).len(), 1);
+
+        let account_list = std::fs::read_to_string("test_data/test_account_list.txt").unwrap();
+        let account_vec: Vec<&str> = account_list.lines().take(500).collect();
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.85, threshold_group_members: 1 };
+        let params = CMinHashLshParams::default();
+        let index = ana.build_min_hash_lsh_index(&params);
+        let candidate_pairs = index.candidate_pairs();
+        assert!(index.bucket_amount() > 0);
+        assert!(candidate_pairs.windows(2).all(|pair| pair[0] < pair[1]) && candidate_pairs.iter().all(|(a, b)| a < b));
+        assert!(ana.build_min_hash_lsh_index(CMinHashLshParams::default().set_bands(0)).candidate_pairs().is_empty());
+        // 各账号的候选账号与候选账号对一致
+        let mut expected_neighbours = vec![Vec::new(); account_vec.len()];
+        for (a, b) in candidate_pairs.iter(){
+            expected_neighbours[*a].push(*b);
+            expected_neighbours[*b].push(*a);
+        }
+        for (account, expected) in expected_neighbours.iter_mut().enumerate(){
+            expected.sort_unstable();
+            assert_eq!(&index.candidate_neighbours(account), expected);
+        }
+
+        let result = ana.group_by_similarity_with_options(&threshold, CGroupingOptions::default().set_strategy(ClusteringStrategy::MinHashLsh(params)));
+        let report = result.min_hash_lsh.clone().unwrap();
+        assert_eq!(report.candidate_pairs, candidate_pairs.len());
+        assert!((report.candidate_pairs as u64) < report.exhaustive_pairs / 4);
+        assert_eq!(report.sampled_accounts, params.recall_sample_size);
+        assert!(report.estimated_recall.unwrap() > 0.8);
+        assert_eq!(result.groups.iter().map(|g| g.size()).sum::<usize>(), ana.to_vec().len());
+
+        // 候选账号对覆盖全部相似账号对时，结果与连通分量策略一致
+        let exhaustive = ana.find_similar_index_pairs(threshold.threshold_sim);
+        let found = exhaustive.iter().filter(|(a, b, _)| candidate_pairs.binary_search(&(*a, *b)).is_ok()).count();
+        assert_eq!(report.similar_pairs, found);
+        let wide = *CMinHashLshParams::default().set_bands(400).set_rows(1).set_recall_sample_size(0).set_max_bucket_size(usize::MAX);
+        let wide_result = ana.group_by_similarity_with_options(&threshold, CGroupingOptions::default().set_strategy(ClusteringStrategy::MinHashLsh(wide)));
+        assert_eq!(wide_result.min_hash_lsh.clone().unwrap().estimated_recall, None);
+        assert_eq!(wide_result.min_hash_lsh.unwrap().similar_pairs, exhaustive.len());
+        let components = ana.group_by_similarity_with_options(&threshold, CGroupingOptions::default().set_strategy(ClusteringStrategy::ConnectedComponents));
+        assert_eq!(wide_result.groups, components.groups);
+
+        // 同一模板批量生成的账号落入巨大的桶，候选账号对的数量受max_bucket_size限制
+        let farm_names: Vec<String> = (0..1000).map(|i| format!("ubut{:04}", (i * 7919) % 10000)).collect();
+        let farm_vec: Vec<&str> = farm_names.iter().map(|name| name.as_str()).collect();
+        let farm = CAccountNameAnaVec::new(&farm_vec);
+        let capped_params = *CMinHashLshParams::default().set_max_bucket_size(8);
+        let capped_index = farm.build_min_hash_lsh_index(&capped_params);
+        let capped_pairs = capped_index.candidate_pairs();
+        assert!(capped_index.oversized_bucket_amount() > 0);
+        assert!(capped_pairs.len() <= capped_params.bands * farm_vec.len() * (capped_params.max_bucket_size - 1));
+        let uncapped_pairs = farm.build_min_hash_lsh_index(CMinHashLshParams::default().set_max_bucket_size(usize::MAX)).candidate_pairs();
+        assert!(capped_pairs.len() * 10 < uncapped_pairs.len());
+        assert!(capped_pairs.iter().all(|pair| uncapped_pairs.binary_search(pair).is_ok()));
+        let farm_threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.8, threshold_group_members: 1 };
+        let capped_result = farm.group_by_similarity_with_options(&farm_threshold, CGroupingOptions::default().set_strategy(ClusteringStrategy::MinHashLsh(*capped_params.clone().set_recall_sample_size(0))));
+        let capped_report = capped_result.min_hash_lsh.unwrap();
+        assert_eq!(capped_report.candidate_pairs, capped_pairs.len());
+        assert_eq!(capped_report.oversized_bucket_amount, capped_index.oversized_bucket_amount());
+        // 排序后相邻的账号仍相互连接，整批账号仍合并为一组
+        assert_eq!(capped_result.groups.len(), 1);
+    }
+}
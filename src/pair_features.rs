@@ -0,0 +1,172 @@
+use std::io::{self, Write};
+use rayon::prelude::*;
+use serde_json::json;
+use crate::analyze_account_name_similarity::{CAccountNameSimAnalyse, CAccountNameSimAnalyseParamsWeightTable, CAccountNameSimResultDetail, DEFAULT_PARAMETER_WEIGHT_TABLE};
+use crate::score_combiner::CScoreCombineContext;
+use crate::similarity_matrix::escape_csv_field;
+
+
+/// 账号对特征的数值列名称，与CPairFeatureRow::values的顺序一致: 相似度细节的全部字段(按声明顺序)、两账号名的长度(字节)以及换算后的编辑相似度
+///
+/// Names of the numeric pair feature columns, in the same order as CPairFeatureRow::values: every field of the similarity detail (in declaration order),
+/// the lengths of both account names (bytes) and the edit similarity
+pub const PAIR_FEATURE_COLUMN_NAMES: [&str; 12] = [
+    "sim_total_score",
+    "sim_score",
+    "sim_jaro_distance",
+    "sim_edit_distance",
+    "sim_length",
+    "sim_item_list",
+    "sim_item_amount",
+    "sim_skeleton_style",
+    "sim_skeleton_part_size_list",
+    "length_a",
+    "length_b",
+    "sim_edit_similarity",
+];
+
+// 单个账号对的特征
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CPairFeatureRow{
+    pub account_name_a: String,
+    pub account_name_b: String,
+    pub label: Option<bool>,                        // 标注(是否同组)，未标注时为None
+    pub detail: CAccountNameSimResultDetail,
+    pub context: CScoreCombineContext
+}
+impl CPairFeatureRow{
+
+    /// 按PAIR_FEATURE_COLUMN_NAMES的顺序返回数值特征
+    ///
+    /// Return the numeric features in the order of PAIR_FEATURE_COLUMN_NAMES
+    pub fn values(&self) -> Vec<f64>{
+        let mut values = self.detail.to_feature_vec();
+        values.push(self.context.length_a as f64);
+        values.push(self.context.length_b as f64);
+        values.push(self.context.edit_similarity(self.detail.sim_edit_distance));
+        values
+    }
+}
+
+/// # Description
+/// * 一批账号对的特征，可用于训练自定义的账号对分类模型。
+/// * The features of a batch of account name pairs, for training custom pairwise classifiers.
+/// # Export
+/// * 支持导出为CSV或JSONL，列为account_name_a、account_name_b、label以及PAIR_FEATURE_COLUMN_NAMES。
+/// * Can be exported as CSV or JSONL. The columns are account_name_a, account_name_b, label and PAIR_FEATURE_COLUMN_NAMES.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CPairFeatureTable{
+    pub rows: Vec<CPairFeatureRow>
+}
+impl CPairFeatureTable{
+
+    /// 多线程计算账号对的特征(默认权重表)，行顺序与pairs一致
+    ///
+    /// Calculate the features of the pairs in parallel (default weight table), the row order is the same as pairs
+    pub fn calc(pairs: &[(&str, &str, Option<bool>)]) -> CPairFeatureTable{
+        CPairFeatureTable::calc_by_specify_param_weights(pairs, &DEFAULT_PARAMETER_WEIGHT_TABLE)
+    }
+
+    /// 多线程计算账号对的特征(需要传入参数权重表)，行顺序与pairs一致
+    ///
+    /// Calculate the features of the pairs in parallel with the given weight table, the row order is the same as pairs
+    pub fn calc_by_specify_param_weights(pairs: &[(&str, &str, Option<bool>)], weight_table: &CAccountNameSimAnalyseParamsWeightTable) -> CPairFeatureTable{
+        let rows = pairs.par_iter()
+            .map(|(name_a, name_b, label)| {
+                let (obj_a, obj_b) = (CAccountNameSimAnalyse::new(name_a), CAccountNameSimAnalyse::new(name_b));
+                CPairFeatureRow{
+                    account_name_a: name_a.to_string(),
+                    account_name_b: name_b.to_string(),
+                    label: *label,
+                    detail: obj_a.calc_similarity_by_specify_param_weights(&obj_b, weight_table).1,
+                    context: CScoreCombineContext::new(&obj_a, &obj_b)
+                }
+            })
+            .collect();
+        CPairFeatureTable{ rows }
+    }
+
+    /// 导出CSV，未标注的账号对label列为空，已标注的为1或0
+    ///
+    /// Export as CSV. The label column is empty for unlabeled pairs, 1 or 0 otherwise
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()>{
+        writeln!(writer, "account_name_a,account_name_b,label,{}", PAIR_FEATURE_COLUMN_NAMES.join(","))?;
+        for row in self.rows.iter(){
+            let label = match row.label {
+                Some(true) => "1",
+                Some(false) => "0",
+                None => ""
+            };
+            write!(writer, "{},{},{}", escape_csv_field(&row.account_name_a), escape_csv_field(&row.account_name_b), label)?;
+            for value in row.values(){
+                write!(writer, ",{}", value)?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// 导出JSONL，每行一个JSON对象，未标注的账号对label为null
+    ///
+    /// Export as JSONL, one JSON object per line. The label is null for unlabeled pairs
+    pub fn write_jsonl<W: Write>(&self, writer: &mut W) -> io::Result<()>{
+        for row in self.rows.iter(){
+            let mut object = json!({
+                "account_name_a": row.account_name_a,
+                "account_name_b": row.account_name_b,
+                "label": row.label
+            });
+            for (name, value) in PAIR_FEATURE_COLUMN_NAMES.iter().zip(row.values()){
+                object[*name] = json!(value);
+            }
+            // 编辑距离及长度为整数
+            object["sim_edit_distance"] = json!(row.detail.sim_edit_distance);
+            object["length_a"] = json!(row.context.length_a);
+            object["length_b"] = json!(row.context.length_b);
+            writeln!(writer, "{}", object)?;
+        }
+        Ok(())
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    #[test]
+    fn it_works() {
+        let pairs = vec![("ubut2739", "ubut1179", Some(true)), ("ubut2739", "zz2871369", Some(false)), ("a,\"b\"", "用户2739", None)];
+        let table = CPairFeatureTable::calc(&pairs);
+        assert_eq!(table.rows.len(), 3);
+        assert_eq!(table.rows[0].detail.sim_total_score,
+                   CAccountNameSimAnalyse::new("ubut2739").calc_similarity(&CAccountNameSimAnalyse::new("ubut1179")).0);
+        assert_eq!(table.rows[0].values().len(), PAIR_FEATURE_COLUMN_NAMES.len());
+        assert_eq!(table.rows[0].values()[9..], [8.0, 8.0, 0.625]);
+        // 编辑相似度按字符数量换算
+        assert_eq!(CPairFeatureTable::calc(&[("用户2739", "用户1179", None)]).rows[0].values()[9..], [10.0, 10.0, 0.5]);
+
+        let mut csv = Vec::new();
+        table.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("ubut2739,ubut1179,1,"));
+        assert!(lines[2].starts_with("ubut2739,zz2871369,0,"));
+        assert!(lines[3].starts_with("\"a,\"\"b\"\"\",用户2739,,"));
+
+        let mut jsonl = Vec::new();
+        table.write_jsonl(&mut jsonl).unwrap();
+        let objects: Vec<Value> = String::from_utf8(jsonl).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(objects.len(), 3);
+        assert_eq!(objects[0]["label"], json!(true));
+        assert_eq!(objects[2]["label"], Value::Null);
+        assert_eq!(objects[0]["sim_edit_distance"], json!(3));
+        assert_eq!(objects[0]["sim_total_score"], json!(table.rows[0].detail.sim_total_score));
+        println!("{}", csv);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use rayon::prelude::*;
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+use crate::grouping_result::CClusteringAnnotations;
+use crate::blocking::BlockingKey;
+
+
+// 分组精修的统计
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CRefinementReport{
+    pub rounds: usize,              // 实际执行的轮数
+    pub merges: usize,              // 合并的组数量
+    pub leader_changes: usize,      // 组长重选为中心点的次数
+    pub moves: usize,               // 移入其他组的组员数量
+    pub b_converged: bool           // 是否在达到轮数上限前收敛(某一轮没有任何变化)
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    // k-medoids式的迭代精修，每轮依次:
+    // 1. 合并组长相似度高于等于阈值的组(组员多的组保留，数量相同时保留组长序号小的组)，单账号组之间同样合并，快速模式下被分到不同分割块的相似账号由此归为一组
+    // 2. 将各组组长重选为中心点
+    // 3. 将每个组员移入与其最相似且达到阈值的组长所在的组(相似度相同时留在原组，其次取组长序号最小者)，不达到任何组长阈值的组员留在原组
+    // 组长及组员只与至少在一个分块键下处于同一块的组长比较，blocking_keys为空时按骨架分块
+    // 某一轮没有任何变化或达到max_rounds时结束。结束后按最终的分组重新计算annotations中以组长为键的直径及模块度
+    pub(crate) fn refine_groups(&self, index_map: HashMap<usize, Vec<usize>>, annotations: &mut CClusteringAnnotations,
+                                threshold_sim: f64, max_rounds: usize, blocking_keys: &[BlockingKey]) -> (HashMap<usize, Vec<usize>>, CRefinementReport){
+        let default_keys = [BlockingKey::default()];
+        let blocking_keys = if blocking_keys.is_empty() { &default_keys[..] } else { blocking_keys };
+        let mut groups: BTreeMap<usize, Vec<usize>> = index_map.into_iter().collect();
+        let mut report = CRefinementReport::default();
+        while report.rounds < max_rounds{
+            report.rounds += 1;
+            let changes_before = (report.merges, report.leader_changes, report.moves);
+            report.merges += self.merge_similar_groups(&mut groups, threshold_sim, blocking_keys);
+            report.leader_changes += self.reelect_medoids_of_groups(&mut groups);
+            report.moves += self.move_members_to_best_leader(&mut groups, threshold_sim, blocking_keys);
+            if (report.merges, report.leader_changes, report.moves) == changes_before{
+                report.b_converged = true;
+                break;
+            }
+        }
+        let index_map: HashMap<usize, Vec<usize>> = groups.into_iter().collect();
+        annotations.refresh_group_annotations(&index_map, self.data_vec_size);
+        (index_map, report)
+    }
+
+    // 合并组长相似的组，返回合并的组数量
+    fn merge_similar_groups(&self, groups: &mut BTreeMap<usize, Vec<usize>>, threshold_sim: f64, blocking_keys: &[BlockingKey]) -> usize{
+        let mut leaders: Vec<usize> = groups.keys().copied().collect();
+        leaders.sort_by_key(|leader| (std::cmp::Reverse(groups[leader].len()), *leader));
+        let position_of: HashMap<usize, usize> = leaders.iter().enumerate().map(|(position, leader)| (*leader, position)).collect();
+        let candidates = self.find_blocked_candidates(&leaders, &leaders, blocking_keys);
+        // 每个组长之后(按处理顺序)同一块中组长相似的组
+        let similar_leaders: Vec<Vec<usize>> = leaders.par_iter()
+            .zip(candidates)
+            .enumerate()
+            .map(|(position, (leader, others))| {
+                let mut later: Vec<usize> = others.into_iter()
+                    .map(|other| position_of[&other])
+                    .filter(|other_position| *other_position > position)
+                    .collect();
+                later.sort_unstable();
+                later.into_iter()
+                    .map(|other_position| leaders[other_position])
+                    .filter(|other| self.analyse_obj(*leader).calc_similarity_at_least(&self.analyse_obj(*other), threshold_sim).is_some())
+                    .collect()
+            })
+            .collect();
+
+        let mut absorbed: HashSet<usize> = HashSet::new();
+        for (leader, others) in leaders.iter().zip(similar_leaders){
+            if absorbed.contains(leader){
+                continue;
+            }
+            for other in others.into_iter().filter(|other| !absorbed.contains(other)).collect::<Vec<usize>>(){
+                absorbed.insert(other);
+                let members = groups.remove(&other).unwrap();
+                groups.get_mut(leader).unwrap().extend(members);
+            }
+        }
+        absorbed.len()
+    }
+
+    // 将各组组长重选为中心点，返回组长发生变化的组数量
+    fn reelect_medoids_of_groups(&self, groups: &mut BTreeMap<usize, Vec<usize>>) -> usize{
+        let reelected: Vec<(usize, usize, Vec<usize>)> = std::mem::take(groups).into_par_iter()
+            .map(|(old_leader, mut members)| {
+                let leader = self.find_medoid(&members);
+                members.retain(|index| *index != leader);
+                members.insert(0, leader);
+                (old_leader, leader, members)
+            })
+            .collect();
+        let mut leader_changes = 0;
+        for (old_leader, leader, members) in reelected{
+            if leader != old_leader{
+                leader_changes += 1;
+            }
+            groups.insert(leader, members);
+        }
+        leader_changes
+    }
+
+    // 将组员移入最匹配的组长所在的组，返回移动的组员数量
+    fn move_members_to_best_leader(&self, groups: &mut BTreeMap<usize, Vec<usize>>, threshold_sim: f64, blocking_keys: &[BlockingKey]) -> usize{
+        let leaders: Vec<usize> = groups.keys().copied().collect();
+        let members: Vec<(usize, usize)> = groups.iter()
+            .flat_map(|(leader, members)| members[1..].iter().map(move |index| (*index, *leader)))
+            .collect();
+        let member_indexes: Vec<usize> = members.iter().map(|(index, _)| *index).collect();
+        let candidates = self.find_blocked_candidates(&leaders, &member_indexes, blocking_keys);
+        let moves: Vec<(usize, usize, usize)> = members.par_iter()
+            .zip(candidates)
+            .filter_map(|((index, current_leader), candidate_leaders)| {
+                let (best_leader, _) = candidate_leaders.iter().chain(std::iter::once(current_leader))
+                    .filter_map(|leader| self.analyse_obj(*leader).calc_similarity_at_least(&self.analyse_obj(*index), threshold_sim)
+                        .map(|(similarity, _)| (*leader, similarity)))
+                    .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal)
+                        .then_with(|| (a.0 == *current_leader).cmp(&(b.0 == *current_leader)))
+                        .then_with(|| b.0.cmp(&a.0)))?;
+                if best_leader == *current_leader { None } else { Some((*index, *current_leader, best_leader)) }
+            })
+            .collect();
+        for (index, from, to) in moves.iter(){
+            groups.get_mut(from).unwrap().retain(|member| member != index);
+            groups.get_mut(to).unwrap().push(*index);
+        }
+        moves.len()
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group_account_name_by_similarity::{CSimilarityGroupingThreshold, CGroupingOptions, EfficiencyMode, ClusteringStrategy};
+    use crate::grouping_result::CAccountGroup;
+    use crate::connected_components::{build_adjacency, calc_group_diameter};
+    use crate::community_detection::calc_community_modularity;
+    use crate::blocking::BlockingKey;
+    #[test]
+    fn it_works() {
+        let account_list = std::fs::read_to_string("test_data/test_account_list.txt").unwrap();
+        let account_vec: Vec<&str> = account_list.lines().take(300).collect();
+        let ana = CAccountNameAnaVec::new(&account_vec);
+        let threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.85, threshold_group_members: 1 };
+        let mut options = CGroupingOptions::default().set_mode(EfficiencyMode::Rapidly).clone();
+        let unrefined = ana.group_by_similarity_with_options(&threshold, &options);
+        assert_eq!(unrefined.refinement, None);
+
+        let refined = ana.group_by_similarity_with_options(&threshold, options.set_refinement_rounds(20));
+        let report = refined.refinement.clone().unwrap();
+        assert!(report.rounds >= 1 && report.rounds <= 20);
+        assert!(report.merges > 0 && report.moves > 0);
+        assert_eq!(refined.groups.iter().map(|g| g.size()).sum::<usize>(), ana.to_vec().len());
+        assert!(refined.group_amount() < unrefined.group_amount());
+        // 该数据在轮数上限内收敛: 收敛后不存在组长相似的组，组员都不能通过移动得到更好的组长
+        assert!(report.b_converged);
+        let index_map = ana.group_account_indexes_by_similarity(&mut threshold.clone(), &options);
+        let (refined_map, _) = ana.refine_groups(index_map, &mut CClusteringAnnotations::default(), 0.85, 20, &[]);
+        let mut groups: BTreeMap<usize, Vec<usize>> = refined_map.into_iter().collect();
+        assert_eq!(ana.merge_similar_groups(&mut groups, 0.85, &[BlockingKey::Skeleton]), 0);
+        assert_eq!(ana.move_members_to_best_leader(&mut groups, 0.85, &[BlockingKey::Skeleton]), 0);
+
+        // 精修后的直径与模块度按最终的分组重新计算
+        let names = ana.to_vec();
+        let name_index: HashMap<&str, usize> = names.iter().enumerate().map(|(index, name)| (name.as_str(), index)).collect();
+        let members_of = |group: &CAccountGroup| group.member_names().iter().map(|name| name_index[name]).collect::<Vec<usize>>();
+        // 按窄窗口排序邻域分块的连通分量中，组长相似的分量会在精修中合并，直径按分块后的相似账号对计算，合并后不连通的组没有直径
+        let narrow_window = [BlockingKey::SortedNeighborhood{ window: 2 }];
+        let similar_pairs = ana.find_similar_index_pairs_by_blocking_keys(0.85, &narrow_window);
+        let components_options = CGroupingOptions::default().set_strategy(ClusteringStrategy::ConnectedComponents)
+            .set_blocking_keys(narrow_window.to_vec()).set_refinement_rounds(20).clone();
+        let components = ana.group_by_similarity_with_options(&threshold, &components_options);
+        let components_report = components.refinement.clone().unwrap();
+        assert!(components_report.merges > 0);
+        assert!(components.groups.iter().any(|group| group.size() > 1 && group.diameter.is_none()));
+        for group in components.groups.iter(){
+            let members = members_of(group);
+            let in_group_pairs: Vec<(usize, usize, f64)> = similar_pairs.iter().filter(|(a, b, _)| members.contains(a) && members.contains(b)).copied().collect();
+            assert_eq!(group.diameter, calc_group_diameter(&build_adjacency(names.len(), &in_group_pairs), &members));
+        }
+
+        // 社区精修后有组合并及组员移动，模块度与精修前不同
+        let community_threshold = CSimilarityGroupingThreshold{ threshold_sim: 0.8, threshold_group_members: 1 };
+        let mut community_options = CGroupingOptions::default().set_strategy(ClusteringStrategy::Community{ edge_floor: 0.9 }).clone();
+        let unrefined_communities = ana.group_by_similarity_with_options(&community_threshold, &community_options);
+        let communities = ana.group_by_similarity_with_options(&community_threshold, community_options.set_refinement_rounds(20));
+        let community_report = communities.refinement.clone().unwrap();
+        assert!(community_report.merges > 0 && community_report.moves > 0);
+        assert_ne!(communities.modularity, unrefined_communities.modularity);
+        let mut community_of = vec![0; names.len()];
+        for (community, group) in communities.groups.iter().enumerate(){
+            for index in members_of(group){
+                community_of[index] = community;
+            }
+        }
+        let expected = calc_community_modularity(&community_of, &ana.find_similar_index_pairs(0.9));
+        assert!((communities.modularity.unwrap() - expected.iter().sum::<f64>()).abs() < 1e-9);
+        for (community, group) in communities.groups.iter().enumerate(){
+            assert!((group.modularity.unwrap() - expected[community]).abs() < 1e-9);
+        }
+
+        // 快速模式下被分到不同分割块、各自成组的两个相似账号在精修中合并
+        let pair_vec = vec!["ubut2739", "ubut2740"];
+        let pair_ana = CAccountNameAnaVec::new(&pair_vec);
+        let singletons: HashMap<usize, Vec<usize>> = vec![(0, vec![0]), (1, vec![1])].into_iter().collect();
+        let (merged, merged_report) = pair_ana.refine_groups(singletons, &mut CClusteringAnnotations::default(), 0.85, 20, &[]);
+        assert_eq!((merged.len(), merged_report.merges), (1, 1));
+
+        // 轮数上限为1时只执行一轮
+        let once = ana.group_by_similarity_with_options(&threshold, options.set_refinement_rounds(1)).refinement.unwrap();
+        assert_eq!(once.rounds, 1);
+    }
+}
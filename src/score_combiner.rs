@@ -0,0 +1,450 @@
+use crate::analyze_account_name_similarity::{CAccountNameSimAnalyse, CAccountNameSimResultDetail};
+
+
+// 避免几何平均中出现ln(0)
+const GEOMETRIC_MEAN_MIN_COMPONENT: f64 = 1e-6;
+// 训练时分项权重的取值范围为 [0, COMBINER_WEIGHT_SEARCH_MAX] 内的整数
+const COMBINER_WEIGHT_SEARCH_MAX: usize = 20;
+// 训练时逻辑回归参数的取值范围为 [-LOGISTIC_PARAM_SEARCH_MAX, LOGISTIC_PARAM_SEARCH_MAX]，步长为LOGISTIC_PARAM_SEARCH_STEP
+const LOGISTIC_PARAM_SEARCH_MAX: f64 = 10.0;
+const LOGISTIC_PARAM_SEARCH_STEP: f64 = 0.5;
+
+/// 通用组合器所使用的分项名称，顺序与detail_components返回值一致
+///
+/// Names of the components used by the generic combiners, in the same order as the values returned by detail_components
+pub const COMBINER_COMPONENT_NAMES: [&str; 8] = [
+    "sim_score",
+    "sim_jaro_distance",
+    "sim_edit_similarity",
+    "sim_length",
+    "sim_item_list",
+    "sim_item_amount",
+    "sim_skeleton_style",
+    "sim_skeleton_part_size_list",
+];
+
+// 组合分数时所需的、相似度细节之外的上下文信息
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CScoreCombineContext{
+    pub length_a: usize,        // 账号名a的长度(字节)
+    pub length_b: usize,        // 账号名b的长度(字节)
+    pub char_amount_a: usize,   // 账号名a的字符数量
+    pub char_amount_b: usize    // 账号名b的字符数量
+}
+impl CScoreCombineContext{
+    pub fn new(obj_a: &CAccountNameSimAnalyse, obj_b: &CAccountNameSimAnalyse) -> CScoreCombineContext{
+        CScoreCombineContext{
+            length_a: obj_a.account_name.len(),
+            length_b: obj_b.account_name.len(),
+            char_amount_a: obj_a.account_name.chars().count(),
+            char_amount_b: obj_b.account_name.chars().count()
+        }
+    }
+
+    // 平均长度(整数除法，与原有计算保持一致)
+    pub fn average_len(&self) -> f64{
+        ((self.length_a + self.length_b) / 2) as f64
+    }
+
+    // 将编辑距离换算为 [0, 1] 区间内的相似度，编辑距离按字符计算，因此除以较长账号名的字符数量
+    pub fn edit_similarity(&self, edit_distance: i64) -> f64{
+        let max_len = self.char_amount_a.max(self.char_amount_b);
+        if max_len == 0 {
+            return 1.0;
+        }
+        (1.0 - edit_distance as f64 / max_len as f64).max(0.0)
+    }
+}
+
+// 单个分项对最终分数的贡献
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CScoreContribution{
+    pub component: &'static str,    // 分项名称
+    pub value: f64,                 // 分项原始值
+    pub contribution: f64           // 该分项对最终分数的贡献
+}
+
+/// 按COMBINER_COMPONENT_NAMES的顺序返回各分项的值
+///
+/// Return the value of each component in the order of COMBINER_COMPONENT_NAMES
+pub fn detail_components(detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> [f64; 8]{
+    [detail.sim_score,
+     detail.sim_jaro_distance,
+     context.edit_similarity(detail.sim_edit_distance),
+     detail.sim_length,
+     detail.sim_item_list,
+     detail.sim_item_amount,
+     detail.sim_skeleton_style,
+     detail.sim_skeleton_part_size_list]
+}
+
+/// # Description
+/// * ScoreCombiner负责将相似度细节中的各分项组合为最终的sim_total_score。
+/// * ScoreCombiner combines the components of the similarity detail into the final sim_total_score.
+/// # Function
+/// * 默认使用CEditDistanceJaroCombiner，也可使用加权平均、几何平均、最大值、逻辑回归，或直接传入闭包 `|detail, context| -> f64`。
+/// * CEditDistanceJaroCombiner is used by default. A weighted mean, geometric mean, max, logistic function or a closure `|detail, context| -> f64` can be used instead.
+pub trait ScoreCombiner: Send + Sync {
+
+    /// 计算最终分数
+    ///
+    /// Calculate the final score
+    fn combine(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> f64;
+
+    /// 各分项对最终分数的贡献，默认将全部分数归于sim_total_score
+    ///
+    /// The contribution of each component to the final score, by default the whole score is attributed to sim_total_score
+    fn contributions(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> Vec<CScoreContribution>{
+        let score = self.combine(detail, context);
+        vec![CScoreContribution{ component: "sim_total_score", value: score, contribution: score }]
+    }
+}
+
+/// # Description
+/// * 可训练的组合器: 暴露一组数值参数及其搜索候选值，train_weight_table_with_combiner会与权重表一同对其做坐标上升搜索。
+/// * A trainable combiner: it exposes a list of numeric parameters and their candidate values, which train_weight_table_with_combiner searches by coordinate ascent together with the weight table.
+pub trait TunableScoreCombiner: ScoreCombiner + Clone {
+
+    /// 当前的全部参数值
+    ///
+    /// The current values of all parameters
+    fn tunable_params(&self) -> Vec<f64>;
+
+    /// 修改第index个参数
+    ///
+    /// Set the parameter at index
+    fn set_tunable_param(&mut self, index: usize, value: f64);
+
+    /// 搜索时第index个参数的候选取值
+    ///
+    /// The candidate values of the parameter at index during the search
+    fn tunable_param_candidates(&self, index: usize) -> Vec<f64>;
+}
+
+// 分项权重的候选取值
+fn combiner_weight_candidates() -> Vec<f64>{
+    (0..=COMBINER_WEIGHT_SEARCH_MAX).map(|weight| weight as f64).collect()
+}
+
+impl<F> ScoreCombiner for F
+    where F: Fn(&CAccountNameSimResultDetail, &CScoreCombineContext) -> f64 + Send + Sync {
+    fn combine(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> f64{
+        self(detail, context)
+    }
+}
+
+
+/// 默认组合方式: 以编辑距离与平均长度为权重，对sim_score与jaro-winkler相似度加权
+///
+/// total = (sim_score * edit_distance + average_len * jaro) / (average_len + edit_distance)
+///
+/// 两账号名差异越大(编辑距离越大)，结构相似度sim_score的占比越高；差异越小，字符层面的jaro-winkler相似度占比越高
+///
+/// The default combiner: blends sim_score and the jaro-winkler similarity, weighted by the edit distance and the average length.
+/// The more two names differ (the larger the edit distance), the more the structural sim_score counts; the closer they are, the more the character level jaro-winkler similarity counts
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CEditDistanceJaroCombiner;
+impl ScoreCombiner for CEditDistanceJaroCombiner{
+    fn combine(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> f64{
+        let average_len = context.average_len();
+        // 两个空账号名
+        if average_len + detail.sim_edit_distance as f64 == 0.0{
+            return detail.sim_jaro_distance;
+        }
+        (detail.sim_score * detail.sim_edit_distance as f64 + average_len * detail.sim_jaro_distance) /
+            (average_len + detail.sim_edit_distance as f64)
+    }
+
+    fn contributions(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> Vec<CScoreContribution>{
+        let average_len = context.average_len();
+        let edit_distance = detail.sim_edit_distance as f64;
+        let denominator = average_len + edit_distance;
+        // 两个空账号名
+        if denominator == 0.0{
+            return vec![CScoreContribution{ component: "sim_jaro_distance", value: detail.sim_jaro_distance, contribution: detail.sim_jaro_distance }];
+        }
+        vec![CScoreContribution{ component: "sim_score", value: detail.sim_score, contribution: detail.sim_score * edit_distance / denominator },
+             CScoreContribution{ component: "sim_jaro_distance", value: detail.sim_jaro_distance, contribution: average_len * detail.sim_jaro_distance / denominator }]
+    }
+}
+
+// 默认组合方式没有可训练的参数
+impl TunableScoreCombiner for CEditDistanceJaroCombiner{
+    fn tunable_params(&self) -> Vec<f64>{
+        Vec::new()
+    }
+    fn set_tunable_param(&mut self, _index: usize, _value: f64){}
+    fn tunable_param_candidates(&self, _index: usize) -> Vec<f64>{
+        Vec::new()
+    }
+}
+
+/// 加权平均，weights的顺序与COMBINER_COMPONENT_NAMES一致
+///
+/// Weighted mean, the order of weights is the same as COMBINER_COMPONENT_NAMES
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CWeightedMeanCombiner{
+    pub weights: [f64; 8]
+}
+impl Default for CWeightedMeanCombiner{
+    // 默认对sim_score、jaro-winkler相似度、编辑距离相似度取平均
+    fn default() -> Self {
+        CWeightedMeanCombiner{ weights: [1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0] }
+    }
+}
+impl ScoreCombiner for CWeightedMeanCombiner{
+    fn combine(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> f64{
+        self.contributions(detail, context).iter().map(|c| c.contribution).sum()
+    }
+
+    fn contributions(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> Vec<CScoreContribution>{
+        let weight_sum: f64 = self.weights.iter().sum();
+        let components = detail_components(detail, context);
+        (0..components.len())
+            .filter(|i| self.weights[*i] != 0.0)
+            .map(|i| CScoreContribution{
+                component: COMBINER_COMPONENT_NAMES[i],
+                value: components[i],
+                contribution: if weight_sum > 0.0 { self.weights[i] * components[i] / weight_sum } else { 0.0 }
+            })
+            .collect()
+    }
+}
+
+impl TunableScoreCombiner for CWeightedMeanCombiner{
+    fn tunable_params(&self) -> Vec<f64>{
+        self.weights.to_vec()
+    }
+    fn set_tunable_param(&mut self, index: usize, value: f64){
+        self.weights[index] = value;
+    }
+    fn tunable_param_candidates(&self, _index: usize) -> Vec<f64>{
+        combiner_weight_candidates()
+    }
+}
+
+/// 加权几何平均，weights的顺序与COMBINER_COMPONENT_NAMES一致，任一分项很低都会显著拉低最终分数
+///
+/// 贡献按各分项在对数空间中的占比分摊
+///
+/// Weighted geometric mean, the order of weights is the same as COMBINER_COMPONENT_NAMES. Any low component pulls the final score down significantly.
+/// Contributions are split by each component's share in log space
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CGeometricMeanCombiner{
+    pub weights: [f64; 8]
+}
+impl Default for CGeometricMeanCombiner{
+    fn default() -> Self {
+        CGeometricMeanCombiner{ weights: [1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0] }
+    }
+}
+impl ScoreCombiner for CGeometricMeanCombiner{
+    fn combine(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> f64{
+        let weight_sum: f64 = self.weights.iter().sum();
+        if weight_sum <= 0.0{
+            return 0.0;
+        }
+        let components = detail_components(detail, context);
+        let log_sum: f64 = (0..components.len())
+            .map(|i| self.weights[i] * components[i].max(GEOMETRIC_MEAN_MIN_COMPONENT).ln())
+            .sum();
+        (log_sum / weight_sum).exp()
+    }
+
+    fn contributions(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> Vec<CScoreContribution>{
+        let score = self.combine(detail, context);
+        let weight_sum: f64 = self.weights.iter().sum();
+        let components = detail_components(detail, context);
+        let log_terms = (0..components.len())
+            .map(|i| self.weights[i] * components[i].max(GEOMETRIC_MEAN_MIN_COMPONENT).ln())
+            .collect::<Vec<f64>>();
+        let log_sum: f64 = log_terms.iter().sum();
+        (0..components.len())
+            .filter(|i| self.weights[*i] != 0.0)
+            .map(|i| CScoreContribution{
+                component: COMBINER_COMPONENT_NAMES[i],
+                value: components[i],
+                // 所有分项均为1时对数和为0，按权重分摊
+                contribution: if log_sum != 0.0 { score * log_terms[i] / log_sum } else { score * self.weights[i] / weight_sum }
+            })
+            .collect()
+    }
+}
+
+impl TunableScoreCombiner for CGeometricMeanCombiner{
+    fn tunable_params(&self) -> Vec<f64>{
+        self.weights.to_vec()
+    }
+    fn set_tunable_param(&mut self, index: usize, value: f64){
+        self.weights[index] = value;
+    }
+    fn tunable_param_candidates(&self, _index: usize) -> Vec<f64>{
+        combiner_weight_candidates()
+    }
+}
+
+/// 取加权后的最大分项并除以最大的权重，使最终分数始终处于 [0, 1] 区间，weights的顺序与COMBINER_COMPONENT_NAMES一致，最终分数全部归于取得最大值的分项
+///
+/// The maximum weighted component divided by the largest weight, so the final score always stays in [0, 1]. The order of weights is the same as COMBINER_COMPONENT_NAMES. The whole score is attributed to the component that reaches the maximum
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CMaxCombiner{
+    pub weights: [f64; 8]
+}
+impl Default for CMaxCombiner{
+    fn default() -> Self {
+        CMaxCombiner{ weights: [1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0] }
+    }
+}
+impl ScoreCombiner for CMaxCombiner{
+    fn combine(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> f64{
+        self.contributions(detail, context).iter().map(|c| c.contribution).sum()
+    }
+
+    fn contributions(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> Vec<CScoreContribution>{
+        let components = detail_components(detail, context);
+        let mut max_index: Option<usize> = None;
+        for i in 0..components.len(){
+            if self.weights[i] == 0.0{
+                continue;
+            }
+            let b_larger = match max_index {
+                Some(m) => self.weights[i] * components[i] > self.weights[m] * components[m],
+                None => true
+            };
+            if b_larger{
+                max_index = Some(i);
+            }
+        }
+        // 按最大的权重归一化，权重大于1时分数也不会超过1
+        let max_weight = self.weights.iter().copied().fold(0.0, f64::max);
+        match max_index {
+            Some(i) if max_weight > 0.0 => vec![CScoreContribution{ component: COMBINER_COMPONENT_NAMES[i], value: components[i], contribution: self.weights[i] * components[i] / max_weight }],
+            _ => Vec::new()
+        }
+    }
+}
+
+impl TunableScoreCombiner for CMaxCombiner{
+    fn tunable_params(&self) -> Vec<f64>{
+        self.weights.to_vec()
+    }
+    fn set_tunable_param(&mut self, index: usize, value: f64){
+        self.weights[index] = value;
+    }
+    fn tunable_param_candidates(&self, _index: usize) -> Vec<f64>{
+        combiner_weight_candidates()
+    }
+}
+
+/// 逻辑回归: sigmoid(intercept + Σ coefficients[i] * component[i])，coefficients的顺序与COMBINER_COMPONENT_NAMES一致
+///
+/// 贡献为各分项在logit空间中的值(coefficients[i] * component[i])，另有一项"intercept"
+///
+/// Logistic function: sigmoid(intercept + Σ coefficients[i] * component[i]), the order of coefficients is the same as COMBINER_COMPONENT_NAMES.
+/// Contributions are given in logit space (coefficients[i] * component[i]), plus an "intercept" entry
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CLogisticCombiner{
+    pub intercept: f64,
+    pub coefficients: [f64; 8]
+}
+impl CLogisticCombiner{
+    pub fn new(intercept: f64, coefficients: [f64; 8]) -> CLogisticCombiner{
+        CLogisticCombiner{ intercept, coefficients }
+    }
+}
+impl ScoreCombiner for CLogisticCombiner{
+    fn combine(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> f64{
+        let z: f64 = self.contributions(detail, context).iter().map(|c| c.contribution).sum();
+        1.0 / (1.0 + (-z).exp())
+    }
+
+    fn contributions(&self, detail: &CAccountNameSimResultDetail, context: &CScoreCombineContext) -> Vec<CScoreContribution>{
+        let components = detail_components(detail, context);
+        let mut result = vec![CScoreContribution{ component: "intercept", value: 1.0, contribution: self.intercept }];
+        for i in 0..components.len(){
+            if self.coefficients[i] != 0.0{
+                result.push(CScoreContribution{ component: COMBINER_COMPONENT_NAMES[i], value: components[i], contribution: self.coefficients[i] * components[i] });
+            }
+        }
+        result
+    }
+}
+
+// 参数依次为intercept及coefficients
+impl TunableScoreCombiner for CLogisticCombiner{
+    fn tunable_params(&self) -> Vec<f64>{
+        let mut params = vec![self.intercept];
+        params.extend_from_slice(&self.coefficients);
+        params
+    }
+    fn set_tunable_param(&mut self, index: usize, value: f64){
+        match index {
+            0 => self.intercept = value,
+            _ => self.coefficients[index - 1] = value
+        }
+    }
+    fn tunable_param_candidates(&self, _index: usize) -> Vec<f64>{
+        let step_amount = (LOGISTIC_PARAM_SEARCH_MAX / LOGISTIC_PARAM_SEARCH_STEP) as i64;
+        (-step_amount..=step_amount).map(|step| step as f64 * LOGISTIC_PARAM_SEARCH_STEP).collect()
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyze_account_name_similarity::DEFAULT_PARAMETER_WEIGHT_TABLE;
+    #[test]
+    fn it_works() {
+        let obj_a = CAccountNameSimAnalyse::new("0ubutz22ae22");
+        let obj_b = CAccountNameSimAnalyse::new("2ubutz10ae57");
+        let context = CScoreCombineContext::new(&obj_a, &obj_b);
+        let (sim, detail) = obj_a.calc_similarity(&obj_b);
+
+        // 默认组合器与calc_similarity结果一致，且各分项贡献之和等于最终分数
+        assert_eq!(obj_a.calc_similarity_by_specify_combiner(&obj_b, &DEFAULT_PARAMETER_WEIGHT_TABLE, &CEditDistanceJaroCombiner).0, sim);
+        assert!((CEditDistanceJaroCombiner.contributions(&detail, &context).iter().map(|c| c.contribution).sum::<f64>() - sim).abs() < 1e-12);
+
+        let combiners: Vec<Box<dyn ScoreCombiner>> = vec![
+            Box::new(CWeightedMeanCombiner::default()),
+            Box::new(CGeometricMeanCombiner::default()),
+            Box::new(CMaxCombiner::default()),
+        ];
+        for combiner in combiners.iter(){
+            let (score, _) = obj_a.calc_similarity_by_specify_combiner(&obj_b, &DEFAULT_PARAMETER_WEIGHT_TABLE, combiner.as_ref());
+            assert!(0.0 < score && score <= 1.0);
+            assert!((combiner.contributions(&detail, &context).iter().map(|c| c.contribution).sum::<f64>() - score).abs() < 1e-12);
+        }
+        assert_eq!(CMaxCombiner::default().contributions(&detail, &context)[0].component, "sim_score");
+        // 权重大于1时按最大的权重归一化，相同分项不同倍数的权重得到相同的分数
+        let scaled_max = CMaxCombiner{ weights: [20.0, 20.0, 20.0, 0.0, 0.0, 0.0, 0.0, 0.0] };
+        assert_eq!(scaled_max.combine(&detail, &context), CMaxCombiner::default().combine(&detail, &context));
+        let identical = CAccountNameSimAnalyse::new("0ubutz22ae22").calc_similarity(&obj_a).1;
+        assert_eq!(CMaxCombiner{ weights: [20.0, 5.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0] }.combine(&identical, &CScoreCombineContext::new(&obj_a, &obj_a)), 1.0);
+        assert_eq!(CWeightedMeanCombiner::default().combine(&detail, &context), (detail.sim_score + detail.sim_jaro_distance + 1.0 - 5.0 / 12.0) / 3.0);
+
+        let logistic = CLogisticCombiner::new(-4.0, [5.0, 3.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(logistic.contributions(&detail, &context).len(), 3);
+        assert!(logistic.combine(&detail, &context) > 0.5);
+
+        // 编辑距离按字符计算，换算相似度时同样使用字符数量
+        let (wide_a, wide_b) = (CAccountNameSimAnalyse::new("用户2739"), CAccountNameSimAnalyse::new("用户1179"));
+        let wide_context = CScoreCombineContext::new(&wide_a, &wide_b);
+        assert_eq!((wide_context.length_a, wide_context.char_amount_a), (10, 6));
+        assert_eq!(wide_context.edit_similarity(wide_a.calc_similarity(&wide_b).1.sim_edit_distance), 0.5);
+
+        // 闭包
+        let closure = |detail: &CAccountNameSimResultDetail, _context: &CScoreCombineContext| detail.sim_jaro_distance;
+        assert_eq!(obj_a.calc_similarity_by_specify_combiner(&obj_b, &DEFAULT_PARAMETER_WEIGHT_TABLE, &closure).0, detail.sim_jaro_distance);
+        println!("{:?}", CEditDistanceJaroCombiner.contributions(&detail, &context));
+    }
+}
@@ -0,0 +1,243 @@
+use std::io::{self, Write};
+use rayon::prelude::*;
+use crate::group_account_name_by_similarity::CAccountNameAnaVec;
+
+
+// 二进制格式的文件头及版本号
+const BINARY_MAGIC: &[u8; 4] = b"ANSM";
+const BINARY_VERSION: u32 = 1;
+const BINARY_KIND_DENSE: u32 = 0;
+const BINARY_KIND_SPARSE: u32 = 1;
+
+
+/// # Description
+/// * 稠密的对称相似度矩阵，按行优先存储，对角线为1。
+/// * A dense symmetric similarity matrix stored in row-major order, with 1 on the diagonal.
+/// # Export
+/// * 支持导出为CSV边列表、NumPy .npy文件以及简单的二进制格式，行列序号与names中的序号一致。
+/// * Can be exported as a CSV edge list, a NumPy .npy file or a simple binary format. Row and column indexes match the indexes in names.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CDenseSimilarityMatrix{
+    pub names: Vec<String>,
+    pub values: Vec<f64>
+}
+
+/// # Description
+/// * 稀疏相似度矩阵，仅保存相似度高于等于阈值的账号对(i < j)，按(i, j)排序。
+/// * A sparse similarity matrix that only keeps the pairs (i < j) whose similarity reaches the threshold, sorted by (i, j).
+/// # Export
+/// * 支持导出为CSV边列表以及简单的二进制格式。
+/// * Can be exported as a CSV edge list or a simple binary format.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CSparseSimilarityMatrix{
+    pub names: Vec<String>,
+    pub threshold_sim: f64,
+    pub edges: Vec<(usize, usize, f64)>
+}
+
+impl CDenseSimilarityMatrix{
+    pub fn size(&self) -> usize{
+        self.names.len()
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64{
+        self.values[row * self.size() + col]
+    }
+
+    /// 导出CSV边列表(account_a,account_b,similarity)，仅包含 i < j 的账号对
+    ///
+    /// Export a CSV edge list (account_a,account_b,similarity) with the pairs i < j only
+    pub fn write_csv_edge_list<W: Write>(&self, writer: &mut W) -> io::Result<()>{
+        writeln!(writer, "account_a,account_b,similarity")?;
+        for row in 0..self.size(){
+            for col in (row + 1)..self.size(){
+                writeln!(writer, "{},{},{}", escape_csv_field(&self.names[row]), escape_csv_field(&self.names[col]), self.get(row, col))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 导出NumPy .npy文件(float64, 形状为(n, n))，账号名顺序需另行通过names保存
+    ///
+    /// Export a NumPy .npy file (float64, shape (n, n)). The account name order has to be saved separately from names
+    pub fn write_npy<W: Write>(&self, writer: &mut W) -> io::Result<()>{
+        let mut header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}", self.size(), self.size());
+        // 魔数(6) + 版本号(2) + 头长度(2) + 头内容 需按64字节对齐，并以换行符结尾
+        let unpadded_len = 6 + 2 + 2 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - unpadded_len % 64) % 64));
+        header.push('\n');
+
+        writer.write_all(b"\x93NUMPY")?;
+        writer.write_all(&[1, 0])?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        for value in self.values.iter(){
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// 导出简单二进制格式，所有数值均为小端序:
+    ///
+    /// "ANSM" | 版本号u32 | 类型u32(0为稠密) | 账号数量u64 | 账号名(长度u32 + UTF-8字节)... | n*n个f64
+    ///
+    /// Export the simple binary format, all numbers are little endian:
+    /// "ANSM" | version u32 | kind u32 (0 for dense) | account amount u64 | account names (length u32 + UTF-8 bytes)... | n*n f64
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()>{
+        write_binary_header(writer, BINARY_KIND_DENSE, &self.names)?;
+        for value in self.values.iter(){
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl CSparseSimilarityMatrix{
+
+    /// 导出CSV边列表(account_a,account_b,similarity)
+    ///
+    /// Export a CSV edge list (account_a,account_b,similarity)
+    pub fn write_csv_edge_list<W: Write>(&self, writer: &mut W) -> io::Result<()>{
+        writeln!(writer, "account_a,account_b,similarity")?;
+        for (row, col, similarity) in self.edges.iter(){
+            writeln!(writer, "{},{},{}", escape_csv_field(&self.names[*row]), escape_csv_field(&self.names[*col]), similarity)?;
+        }
+        Ok(())
+    }
+
+    /// 导出简单二进制格式，所有数值均为小端序:
+    ///
+    /// "ANSM" | 版本号u32 | 类型u32(1为稀疏) | 账号数量u64 | 账号名(长度u32 + UTF-8字节)... | 边数量u64 | 边(i u32, j u32, 相似度f64)...
+    ///
+    /// Export the simple binary format, all numbers are little endian:
+    /// "ANSM" | version u32 | kind u32 (1 for sparse) | account amount u64 | account names (length u32 + UTF-8 bytes)... | edge amount u64 | edges (i u32, j u32, similarity f64)...
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()>{
+        write_binary_header(writer, BINARY_KIND_SPARSE, &self.names)?;
+        writer.write_all(&(self.edges.len() as u64).to_le_bytes())?;
+        for (row, col, similarity) in self.edges.iter(){
+            writer.write_all(&(*row as u32).to_le_bytes())?;
+            writer.write_all(&(*col as u32).to_le_bytes())?;
+            writer.write_all(&similarity.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, P: Sync> CAccountNameAnaVec<'a, P>{
+
+    /// 多线程计算两两之间的相似度，返回稠密的对称矩阵。每个无序账号对(i < j)只计算一次
+    ///
+    /// 矩阵大小为n*n，仅适用于数千条规模的账号集合
+    ///
+    /// Calculate the pairwise similarity in parallel and return a dense symmetric matrix. Each unordered pair (i < j) is calculated only once.
+    /// The matrix has n*n entries, so it is only suitable for sets of a few thousand accounts
+    pub fn calc_similarity_matrix(&self) -> CDenseSimilarityMatrix{
+        let size = self.data_vec_size;
+        let rows: Vec<Vec<f64>> = (0..size).into_par_iter()
+            .map(|row| ((row + 1)..size)
+                .map(|col| self.analyse_obj(row).calc_similarity(&self.analyse_obj(col)).0)
+                .collect())
+            .collect();
+
+        let mut values = vec![1.0; size * size];
+        for (row, row_values) in rows.iter().enumerate(){
+            for (offset, similarity) in row_values.iter().enumerate(){
+                let col = row + 1 + offset;
+                values[row * size + col] = *similarity;
+                values[col * size + row] = *similarity;
+            }
+        }
+        CDenseSimilarityMatrix{ names: self.to_vec(), values }
+    }
+
+    /// 多线程计算两两之间的相似度，仅保留相似度高于等于threshold_sim的账号对，会使用相似度上界跳过不可能达到阈值的账号对
+    ///
+    /// Calculate the pairwise similarity in parallel and only keep the pairs whose similarity reaches threshold_sim. Pairs that cannot reach it are skipped using the similarity upper bound
+    pub fn calc_sparse_similarity_matrix(&self, threshold_sim: f64) -> CSparseSimilarityMatrix{
+        CSparseSimilarityMatrix{ names: self.to_vec(), threshold_sim, edges: self.find_similar_index_pairs(threshold_sim) }
+    }
+
+    // 多线程查找相似度高于等于阈值的全部账号对(i < j)，按(i, j)排序
+    pub(crate) fn find_similar_index_pairs(&self, threshold_sim: f64) -> Vec<(usize, usize, f64)>{
+        let size = self.data_vec_size;
+        (0..size).into_par_iter()
+            .flat_map_iter(|row| ((row + 1)..size)
+                .filter_map(move |col| self.analyse_obj(row).calc_similarity_at_least(&self.analyse_obj(col), threshold_sim)
+                    .map(|(similarity, _)| (row, col, similarity))))
+            .collect()
+    }
+}
+
+
+fn write_binary_header<W: Write>(writer: &mut W, kind: u32, names: &[String]) -> io::Result<()>{
+    writer.write_all(BINARY_MAGIC)?;
+    writer.write_all(&BINARY_VERSION.to_le_bytes())?;
+    writer.write_all(&kind.to_le_bytes())?;
+    writer.write_all(&(names.len() as u64).to_le_bytes())?;
+    for name in names{
+        writer.write_all(&(name.len() as u32).to_le_bytes())?;
+        writer.write_all(name.as_bytes())?;
+    }
+    Ok(())
+}
+
+// 含逗号、引号或换行的字段需要加引号，字段内的引号需要转义
+pub(crate) fn escape_csv_field(field: &str) -> String{
+    if field.contains([',', '"', '\n', '\r']){
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }else {
+        field.to_string()
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn it_works() {
+        let account_vec = vec!["ubut2739", "ubut1179", "ubut2222", "zz2871369", "htgt4303", "a,\"b\""];
+        let ana = CAccountNameAnaVec::new(&account_vec);
+
+        let dense = ana.calc_similarity_matrix();
+        assert_eq!(dense.values.len(), 36);
+        for row in 0..dense.size(){
+            assert_eq!(dense.get(row, row), 1.0);
+            for col in 0..dense.size(){
+                assert_eq!(dense.get(row, col), dense.get(col, row));
+            }
+        }
+
+        let sparse = ana.calc_sparse_similarity_matrix(0.7);
+        let expected_edges = (0..dense.size())
+            .flat_map(|row| ((row + 1)..dense.size()).map(move |col| (row, col)))
+            .filter(|(row, col)| dense.get(*row, *col) >= 0.7)
+            .map(|(row, col)| (row, col, dense.get(row, col)))
+            .collect::<Vec<(usize, usize, f64)>>();
+        assert_eq!(sparse.edges, expected_edges);
+
+        let mut csv = Vec::new();
+        dense.write_csv_edge_list(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 1 + 15);
+        assert!(csv.contains("\"a,\"\"b\"\"\""));
+
+        let mut npy = Vec::new();
+        dense.write_npy(&mut npy).unwrap();
+        let header_len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!(&npy[..6], b"\x93NUMPY");
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(npy.len(), 10 + header_len + 36 * 8);
+
+        let mut binary = Vec::new();
+        sparse.write_binary(&mut binary).unwrap();
+        let names_len: usize = sparse.names.iter().map(|n| 4 + n.len()).sum();
+        assert_eq!(&binary[..4], b"ANSM");
+        assert_eq!(binary.len(), 4 + 4 + 4 + 8 + names_len + 8 + sparse.edges.len() * 16);
+        println!("{}", csv);
+    }
+}
@@ -0,0 +1,323 @@
+use std::cmp::Ordering;
+use rayon::prelude::*;
+use crate::analyze_account_name_similarity::{CAccountNameSimAnalyse, CAccountNameSimAnalyseParamsWeightTable, CAccountNameSimResultDetail,
+                                             NameComparison, DEFAULT_PARAMETER_WEIGHT_TABLE, WEIGHT_TABLE_PRESETS, combine_result_detail};
+use crate::score_combiner::{CScoreCombineContext, CEditDistanceJaroCombiner, ScoreCombiner, TunableScoreCombiner};
+
+
+// 搜索时每项权重的取值范围为 [0, WEIGHT_SEARCH_MAX]
+const WEIGHT_SEARCH_MAX: usize = 20;
+// 坐标上升的最大轮数
+const MAX_SEARCH_ROUNDS: usize = 10;
+
+
+// 训练目标
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrainingObjective {
+    F1 = 0,     // 最大化目标阈值下的F1
+    Auc = 1     // 最大化ROC曲线下面积
+}
+
+// 权重表在标注样本上的评估结果
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CWeightTableEvaluation{
+    pub threshold_sim: f64,     // 评估所用的相似度阈值
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    pub auc: f64
+}
+
+// 训练报告
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CWeightTrainingReport{
+    pub objective: TrainingObjective,
+    pub pair_amount: usize,                                     // 样本数量
+    pub positive_pair_amount: usize,                            // 正样本(同组)数量
+    pub evaluated_table_amount: usize,                          // 搜索过程中评估过的权重表数量
+    pub default_evaluation: CWeightTableEvaluation,             // 搜索起点(默认权重表及传入的组合器)的评估结果
+    pub trained_evaluation: CWeightTableEvaluation              // 训练所得权重表的评估结果
+}
+
+// 与权重无关的分项相似度缓存，避免每次评估都重新计算编辑距离等耗时项
+struct CPairComponents{
+    detail: CAccountNameSimResultDetail,
+    context: CScoreCombineContext,
+    b_same_group: bool
+}
+
+
+/// 由分组标注生成标注样本: 同组内的账号两两构成正样本，不同组之间的账号两两构成负样本
+///
+/// Generate labeled pairs from labeled groups: accounts in the same group form positive pairs, accounts in different groups form negative pairs
+pub fn generate_labeled_pairs_by_groups<'a>(labeled_groups: &[Vec<&'a str>]) -> Vec<(&'a str, &'a str, bool)>{
+    let mut result = Vec::new();
+    for (group_index, group) in labeled_groups.iter().enumerate(){
+        for (member_index, member) in group.iter().enumerate(){
+            for other in &group[member_index + 1..]{
+                result.push((*member, *other, true));
+            }
+            for other_group in &labeled_groups[group_index + 1..]{
+                for other in other_group{
+                    result.push((*member, *other, false));
+                }
+            }
+        }
+    }
+    result
+}
+
+/// 在标注样本上评估权重表，threshold_sim为判定同组的相似度阈值
+///
+/// Evaluate a weight table on labeled pairs, threshold_sim is the similarity threshold for judging two accounts as the same group
+pub fn evaluate_weight_table(labeled_pairs: &[(&str, &str, bool)], weight_table: &CAccountNameSimAnalyseParamsWeightTable, threshold_sim: f64) -> CWeightTableEvaluation{
+    evaluate_weight_table_with_combiner(labeled_pairs, weight_table, &CEditDistanceJaroCombiner, threshold_sim)
+}
+
+/// 使用指定的组合器在标注样本上评估权重表
+///
+/// Evaluate a weight table on labeled pairs with the given combiner
+pub fn evaluate_weight_table_with_combiner(labeled_pairs: &[(&str, &str, bool)], weight_table: &CAccountNameSimAnalyseParamsWeightTable,
+                                           combiner: &dyn ScoreCombiner, threshold_sim: f64) -> CWeightTableEvaluation{
+    evaluate_components(&prepare_pair_components(labeled_pairs, NameComparison::WholeName), weight_table, combiner, threshold_sim)
+}
+
+/// 在同一批标注样本上按各预设的比较方式评估全部预设，按WEIGHT_TABLE_PRESETS的顺序返回(预设名, 评估结果)
+///
+/// Evaluate every preset on the same labeled pairs with its own name comparison, and return (preset name, evaluation) in the order of WEIGHT_TABLE_PRESETS
+pub fn compare_weight_table_presets(labeled_pairs: &[(&str, &str, bool)], threshold_sim: f64) -> Vec<(&'static str, CWeightTableEvaluation)>{
+    let whole_name_components = prepare_pair_components(labeled_pairs, NameComparison::WholeName);
+    // 邮箱型预设只比较本地部分，单独准备分项
+    let local_part_components = prepare_pair_components(labeled_pairs, NameComparison::EmailLocalPart);
+    WEIGHT_TABLE_PRESETS.iter()
+        .map(|preset| {
+            let components = match preset.name_comparison {
+                NameComparison::WholeName => &whole_name_components,
+                NameComparison::EmailLocalPart => &local_part_components
+            };
+            (preset.name, evaluate_components(components, &preset.weight_table, &CEditDistanceJaroCombiner, threshold_sim))
+        })
+        .collect()
+}
+
+/// # 功能
+/// 使用带标注的账号名对(是否同组)搜索权重表，使其在目标阈值下的F1或AUC最大。
+/// 以默认权重表为起点，对每项权重在 [0, 20] 的整数范围内做坐标上升搜索，直到结果不再提升。
+/// 样本按完整的账号名计算。样本中必须同时包含正负样本，否则返回None。
+///
+/// # Function
+/// Search the weight table on labeled account name pairs (same group or not) to maximize F1 at the target threshold or AUC.
+/// Starting from the default weight table, each weight is searched over the integers in [0, 20] by coordinate ascent until the result stops improving.
+/// Pairs are scored on whole account names. Returns None unless both positive and negative pairs are present.
+pub fn train_weight_table(labeled_pairs: &[(&str, &str, bool)], objective: TrainingObjective, threshold_sim: f64) -> Option<(CAccountNameSimAnalyseParamsWeightTable, CWeightTrainingReport)>{
+    train_weight_table_with_combiner(labeled_pairs, objective, threshold_sim, &CEditDistanceJaroCombiner)
+        .map(|(weight_table, _combiner, report)| (weight_table, report))
+}
+
+/// # 功能
+/// 与train_weight_table相同，但使用传入的组合器计算最终分数，并将组合器的参数(见TunableScoreCombiner)与权重表一同做坐标上升搜索。
+/// 以默认权重表及传入的组合器为起点，返回 (权重表, 组合器, 训练报告)。
+///
+/// # Function
+/// Same as train_weight_table, but the final score is calculated by the given combiner, whose parameters (see TunableScoreCombiner) are searched by coordinate ascent together with the weight table.
+/// Starting from the default weight table and the given combiner, returns (weight table, combiner, training report).
+pub fn train_weight_table_with_combiner<C: TunableScoreCombiner>(labeled_pairs: &[(&str, &str, bool)], objective: TrainingObjective, threshold_sim: f64,
+                                                                 combiner: &C) -> Option<(CAccountNameSimAnalyseParamsWeightTable, C, CWeightTrainingReport)>{
+    let components = prepare_pair_components(labeled_pairs, NameComparison::WholeName);
+    let positive_pair_amount = components.iter().filter(|c| c.b_same_group).count();
+    if positive_pair_amount == 0 || positive_pair_amount == components.len(){
+        return None;
+    }
+
+    let objective_value = |evaluation: &CWeightTableEvaluation| match objective {
+        TrainingObjective::F1 => evaluation.f1,
+        TrainingObjective::Auc => evaluation.auc
+    };
+
+    let default_evaluation = evaluate_components(&components, &DEFAULT_PARAMETER_WEIGHT_TABLE, combiner, threshold_sim);
+    let mut best_table = DEFAULT_PARAMETER_WEIGHT_TABLE;
+    let mut best_combiner = combiner.clone();
+    let mut best_value = objective_value(&default_evaluation);
+    let mut evaluated_table_amount = 1;
+    // 前5个坐标为权重表的各项权重，其后为组合器的参数
+    let coordinate_amount = 5 + combiner.tunable_params().len();
+
+    for _round in 0..MAX_SEARCH_ROUNDS{
+        let mut b_improved = false;
+        for coordinate in 0..coordinate_amount{
+            // 并行评估当前坐标的全部取值，取值相同时保留较早的候选以保证结果稳定
+            let candidates: Vec<(CAccountNameSimAnalyseParamsWeightTable, C)> = if coordinate < 5 {
+                (0..=WEIGHT_SEARCH_MAX)
+                    .map(|weight| with_weight(&best_table, coordinate, weight as f64))
+                    .filter(|table| table.validate().is_ok() && *table != best_table)
+                    .map(|table| (table, best_combiner.clone()))
+                    .collect()
+            }else {
+                let param_index = coordinate - 5;
+                let current_value = best_combiner.tunable_params()[param_index];
+                best_combiner.tunable_param_candidates(param_index).into_iter()
+                    .filter(|value| *value != current_value)
+                    .map(|value| {
+                        let mut candidate_combiner = best_combiner.clone();
+                        candidate_combiner.set_tunable_param(param_index, value);
+                        (best_table.clone(), candidate_combiner)
+                    })
+                    .collect()
+            };
+            evaluated_table_amount += candidates.len();
+            let values = candidates.par_iter()
+                .map(|(table, candidate_combiner)| objective_value(&evaluate_components(&components, table, candidate_combiner, threshold_sim)))
+                .collect::<Vec<f64>>();
+            for ((table, candidate_combiner), value) in candidates.into_iter().zip(values){
+                if value > best_value + 1e-12{
+                    best_value = value;
+                    best_table = table;
+                    best_combiner = candidate_combiner;
+                    b_improved = true;
+                }
+            }
+        }
+        if !b_improved{
+            break;
+        }
+    }
+
+    let trained_evaluation = evaluate_components(&components, &best_table, &best_combiner, threshold_sim);
+    Some((best_table, best_combiner, CWeightTrainingReport{
+        objective,
+        pair_amount: components.len(),
+        positive_pair_amount,
+        evaluated_table_amount,
+        default_evaluation,
+        trained_evaluation
+    }))
+}
+
+
+// 按name_comparison取出实际参与比较的部分，与CAccountNameSimAnalyse::calc_similarity_by_preset的计算方式一致
+fn prepare_pair_components(labeled_pairs: &[(&str, &str, bool)], name_comparison: NameComparison) -> Vec<CPairComponents>{
+    labeled_pairs.par_iter()
+        .map(|(name_a, name_b, b_same_group)| {
+            let (obj_a, obj_b) = (CAccountNameSimAnalyse::new(name_comparison.compared_part(name_a)), CAccountNameSimAnalyse::new(name_comparison.compared_part(name_b)));
+            CPairComponents{
+                detail: obj_a.calc_similarity(&obj_b).1,
+                context: CScoreCombineContext::new(&obj_a, &obj_b),
+                b_same_group: *b_same_group
+            }
+        })
+        .collect()
+}
+
+fn with_weight(table: &CAccountNameSimAnalyseParamsWeightTable, weight_index: usize, weight: f64) -> CAccountNameSimAnalyseParamsWeightTable{
+    let mut result = table.clone();
+    match weight_index {
+        0 => result.skeleton_skeleton_style = weight,
+        1 => result.skeleton_part_size_list = weight,
+        2 => result.length = weight,
+        3 => result.item_list = weight,
+        _ => result.item_amount = weight
+    }
+    result
+}
+
+fn evaluate_components(components: &[CPairComponents], weight_table: &CAccountNameSimAnalyseParamsWeightTable, combiner: &dyn ScoreCombiner, threshold_sim: f64) -> CWeightTableEvaluation{
+    let mut scored: Vec<(f64, bool)> = Vec::with_capacity(components.len());
+    for component in components{
+        let mut detail = component.detail.clone();
+        combine_result_detail(&mut detail, weight_table, &component.context, combiner);
+        scored.push((detail.sim_total_score, component.b_same_group));
+    }
+
+    let true_positive = scored.iter().filter(|s| s.1 && s.0 >= threshold_sim).count() as f64;
+    let predicted_positive = scored.iter().filter(|s| s.0 >= threshold_sim).count() as f64;
+    let actual_positive = scored.iter().filter(|s| s.1).count() as f64;
+    let precision = if predicted_positive > 0.0 { true_positive / predicted_positive } else { 0.0 };
+    let recall = if actual_positive > 0.0 { true_positive / actual_positive } else { 0.0 };
+    let f1 = if precision + recall > 0.0 { 2.0 * precision * recall / (precision + recall) } else { 0.0 };
+
+    CWeightTableEvaluation{ threshold_sim, precision, recall, f1, auc: calc_auc(&mut scored) }
+}
+
+// 基于秩和(Mann-Whitney U)计算AUC，分数相同的样本取平均秩
+fn calc_auc(scored: &mut [(f64, bool)]) -> f64{
+    scored.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    let positive_amount = scored.iter().filter(|s| s.1).count() as f64;
+    let negative_amount = scored.len() as f64 - positive_amount;
+    if positive_amount == 0.0 || negative_amount == 0.0{
+        return 0.0;
+    }
+
+    let mut positive_rank_sum = 0.0;
+    let mut index = 0;
+    while index < scored.len(){
+        let mut end = index;
+        while end < scored.len() && scored[end].0 == scored[index].0{
+            end += 1;
+        }
+        let average_rank = (index + 1 + end) as f64 / 2.0;
+        positive_rank_sum += average_rank * scored[index..end].iter().filter(|s| s.1).count() as f64;
+        index = end;
+    }
+    (positive_rank_sum - positive_amount * (positive_amount + 1.0) / 2.0) / (positive_amount * negative_amount)
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score_combiner::{CWeightedMeanCombiner, CLogisticCombiner};
+    use crate::analyze_account_name_similarity::{CWeightTablePreset, EMAIL_PARAMETER_WEIGHT_TABLE};
+    use crate::algorithm::extract_email_local_part;
+    #[test]
+    fn it_works() {
+        let labeled_groups = vec![
+            vec!["ubut2739", "ubut1179", "ubut2222", "ubut1057"],
+            vec!["htgt4303", "htgt4410", "htgt0021"],
+            vec!["33xwb656", "33xwb778", "33xwb012"],
+            vec!["uvgv2965"], vec!["zz2871369"], vec!["u0j2e9u1s2h8l91"], vec!["t9x1h8y0b7g6f42"],
+        ];
+        let labeled_pairs = generate_labeled_pairs_by_groups(&labeled_groups);
+        assert_eq!(labeled_pairs.len(), 14 * 13 / 2);
+        assert_eq!(labeled_pairs.iter().filter(|p| p.2).count(), 6 + 3 + 3);
+
+        let (table, report) = train_weight_table(&labeled_pairs, TrainingObjective::F1, 0.8).unwrap();
+        assert!(report.trained_evaluation.f1 >= report.default_evaluation.f1);
+        assert_eq!(evaluate_weight_table(&labeled_pairs, &table, 0.8), report.trained_evaluation);
+        let (_table, report) = train_weight_table(&labeled_pairs, TrainingObjective::Auc, 0.8).unwrap();
+        assert!(report.trained_evaluation.auc >= report.default_evaluation.auc);
+        println!("{:?}\n{:?}", table, report);
+
+        assert_eq!(calc_auc(&mut [(0.1, false), (0.5, true), (0.5, false), (0.9, true)]), 0.875);
+        assert!(train_weight_table(&labeled_pairs[..1], TrainingObjective::F1, 0.8).is_none());
+
+        // 组合器的参数与权重表一同搜索
+        let (mean_table, mean_combiner, mean_report) = train_weight_table_with_combiner(&labeled_pairs, TrainingObjective::F1, 0.8, &CWeightedMeanCombiner::default()).unwrap();
+        assert!(mean_report.trained_evaluation.f1 >= mean_report.default_evaluation.f1);
+        assert!(mean_report.evaluated_table_amount > report.evaluated_table_amount);
+        assert_eq!(evaluate_weight_table_with_combiner(&labeled_pairs, &mean_table, &mean_combiner, 0.8), mean_report.trained_evaluation);
+        // 全零系数时分数恒为0.5，低于阈值，只有搜索组合器参数才能提升F1
+        let logistic = CLogisticCombiner::new(0.0, [0.0; 8]);
+        let (_table, trained_logistic, logistic_report) = train_weight_table_with_combiner(&labeled_pairs, TrainingObjective::F1, 0.8, &logistic).unwrap();
+        assert!(logistic_report.trained_evaluation.f1 > logistic_report.default_evaluation.f1);
+        assert_ne!(trained_logistic, logistic);
+
+        let comparison = compare_weight_table_presets(&labeled_pairs, 0.8);
+        assert_eq!(comparison.iter().map(|c| c.0).collect::<Vec<&str>>(), CWeightTablePreset::names());
+        assert_eq!(comparison[0].1, report.default_evaluation);
+        for (name, evaluation) in comparison.iter(){
+            println!("{}: {:?}", name, evaluation);
+        }
+
+        // 邮箱型预设只比较本地部分，其评估结果与权重表在本地部分上的评估结果一致
+        let email_pairs = vec![("john.smith88@gmail.com", "john.smith1990@outlook.com", true), ("mary_lee2020@gmail.com", "mary_lee2021@yahoo.com", true),
+                               ("john.smith88@gmail.com", "mary_lee2020@gmail.com", false), ("x7kq9m2p@gmail.com", "mary_lee2021@gmail.com", false)];
+        let local_part_pairs: Vec<(&str, &str, bool)> = email_pairs.iter().map(|(a, b, same)| (extract_email_local_part(a), extract_email_local_part(b), *same)).collect();
+        let email_evaluation = evaluate_weight_table(&local_part_pairs, &EMAIL_PARAMETER_WEIGHT_TABLE, 0.8);
+        assert_eq!(compare_weight_table_presets(&email_pairs, 0.8).last().unwrap(), &("email", email_evaluation));
+    }
+}